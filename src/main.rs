@@ -1,34 +1,30 @@
 #![allow(dead_code, unused_imports)]
 
-use smol::{Executor};
-
 pub mod secrets;
 
-use swampyer::{WampClient, WampData, wdata, WampArray, WampHash};
+use swampyer::{WampClient, WampClientBuilder, SessionDetails, WampData, wdata, WampArray, WampHash};
 // use swampyer::{wdata, WampData};
 
-fn onjoin (wamp: &mut WampClient, message:Box<WampData>) {
-    // println!("ONJOINCALLED! {:?}", message);
-    println!("ONJOINCALLED!");
-    smol::block_on(async {
-        wamp.call("auth.whoami", wdata!([]), wdata!({})).await;
-    });
+async fn onjoin (mut wamp: WampClient, details: SessionDetails) {
+    println!("ONJOINCALLED! session {}", details.session_id);
+    match wamp.call("auth.whoami", wdata!([]), wdata!({})).await {
+        Ok(result) => println!("whoami: {:?}", result),
+        Err(e) => println!("whoami failed: {:?}", e),
+    }
 }
 
 fn main() {
     println!("Connecting to {}", secrets::URL);
     smol::block_on(async {
-        let mut client = WampClient::connect(
-                              secrets::URL,
-                              secrets::REALM,
-                              secrets::USERNAME,
-                              secrets::PASSWORD,
-                          ).await;
-        let ex = Executor::new();
-            ex.run(async {
-            client.onjoin(onjoin);
-            client.run().await;
-        }).await;
+        let mut client = WampClientBuilder::default()
+                              .url(secrets::URL)
+                              .realm(secrets::REALM)
+                              .ticket(secrets::USERNAME, secrets::PASSWORD)
+                              .build()
+                              .unwrap();
+        client.onjoin(onjoin).await;
+        if let Err(e) = client.run().await {
+            println!("Connection lost: {:?}", e);
+        }
     });
 }
-
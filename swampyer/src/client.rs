@@ -1,14 +1,15 @@
 // To manage concurrency
-use smol::Executor;
 use async_channel::{unbounded, Sender, Receiver};
+use futures::future::{self, Either};
 
 // Debugging
 use std::mem;
 
 use std::sync::Arc;
 use async_mutex::Mutex;
+use std::future::Future;
 
-use std::{ thread, time };
+use std::time::{Duration, Instant};
 
 // To help us build Builders
 use derive_builder::Builder;
@@ -17,50 +18,103 @@ use derive_builder::Builder;
 use std::collections::HashMap;
 
 pub mod transport;
+pub mod runtime;
+pub use runtime::Runtime;
+#[cfg(feature = "smol")]
+pub use runtime::SmolRuntime;
+#[cfg(feature = "tokio")]
+pub use runtime::TokioRuntime;
+use transport::Frame;
+pub mod auth;
+pub use auth::*;
+mod config;
+pub use config::*;
+mod handlers;
+mod call;
+pub use call::*;
+mod options;
+pub use options::*;
+mod meta;
+pub use meta::*;
+pub mod payload;
+pub use payload::*;
+#[cfg(feature = "serde")]
+mod typed;
+mod procedure;
+pub use procedure::Procedure;
+mod subscriber;
+pub use subscriber::Subscriber;
+pub use handlers::{BoxFuture, SessionDetails, RouterFeatures, Event, Invocation, InvocationDetails, Yield, RpcError};
+use handlers::{JoinHandler, EventHandler, EventErrorHandler, InvocationHandler};
+
 use crate::serialization::WampData;
-use crate::wdata;
+use crate::message::{WampMessage, args_from, kwargs_from};
 use crate::{WampError, WampHash, WampArray};
 
-const WAMP_HELLO:u64 = 1;
-const WAMP_WELCOME:u64 = 2;
-const WAMP_CHALLENGE:u64 = 4;
-const WAMP_AUTHENTICATE:u64 = 5;
-const WAMP_ERROR:u64 = 8;
-const WAMP_PUBLISH:u64 = 16;
-const WAMP_PUBLISHED:u64 = 17;
-const WAMP_SUBSCRIBE:u64 = 32;
-const WAMP_SUBSCRIBED:u64 = 33;
-const WAMP_EVENT:u64 = 36;
-const WAMP_CALL:u64 = 48;
-const WAMP_RESULTS:u64 = 50;
-
-
-struct ConnectionInfo {
-    url: String,
-    realm: String,
-    username: String,
-    password: String,
+#[derive(Clone)]
+struct Subscription {
+    topic: String,
+    options: SubscribeOptions,
+    // The router hands out the same id when we subscribe to a topic
+    // twice, so every handler subscribed under it gets the event
+    handlers: Vec<EventHandler>,
+    // Where history replay picks up after a reconnect
+    last_publication: Option<u64>,
 }
 
-type JoinFn = fn(&mut WampClient, Box<WampData>);
+#[derive(Clone)]
+struct Registration {
+    procedure: String,
+    options: RegisterOptions,
+    handler: InvocationHandler,
+}
 
 #[derive(Default, Builder)]
-#[builder(setter(into))]
+#[builder(setter(into), pattern = "owned")]
 struct Tracker {
     #[builder(default = "None")]
-    onjoin: Option<JoinFn>,
+    onjoin: Option<JoinHandler>,
+
+    // Told about events a typed handler couldn't decode
+    #[builder(default = "None")]
+    onevent_error: Option<EventErrorHandler>,
 
     #[builder(default = "1032354")]
     message_index: u64,
 
     #[builder(default = "HashMap::new()")]
-    requests_pending: HashMap<u64, Sender<Box<WampData>>>,
+    requests_pending: HashMap<u64, Sender<WampMessage>>,
 
+    // Progressive invocations still receiving chunks from the caller
+    #[builder(default = "HashMap::new()")]
+    invocations_pending: HashMap<u64, Sender<Invocation>>,
+
+    // Connected but not yet being driven by run()
+    #[builder(default = "None")]
+    transport: Option<transport::Transport>,
+
+    // Frames waiting to be written to the current connection
     #[builder(default = "None")]
-    message_sender: Option<Sender<Vec<u8>>>,
+    outgoing: Option<Sender<Frame>>,
+
+    #[builder(default = "None")]
+    outgoing_receiver: Option<Receiver<Frame>>,
+
+    // Keyed by the ids the router handed back so overlapping
+    // subscriptions each get their own handler
+    #[builder(default = "HashMap::new()")]
+    subscriptions: HashMap<u64, Subscription>,
+
+    #[builder(default = "HashMap::new()")]
+    registrations: HashMap<u64, Registration>,
 
+    // What the router told us in WELCOME
     #[builder(default = "None")]
-    message_receiver: Option<Receiver<Vec<u8>>>,
+    session: Option<SessionDetails>,
+
+    // Installed again on every new session
+    #[builder(default = "Vec::new()")]
+    testaments: Vec<Testament>,
 }
 
 impl Tracker {
@@ -72,254 +126,770 @@ impl Tracker {
 
 #[derive(Clone)]
 pub struct WampClient {
-    info: Arc<ConnectionInfo>,
-    transport: transport::Transport,
+    config: Arc<ClientConfig>,
     tracker: Arc<Mutex<Tracker>>,
-    thread_stack_size: usize,
+    runtime: Arc<dyn Runtime>,
 }
 
 impl WampClient {
 
-    pub async fn authenticate(&mut self) {
-        let details = wdata!({
-                            "authid": (self.info.username.clone()),
-                            "agent": "swampyer-rs",
-                            "authmethods": [ "ticket" ],
-                            "roles": {
-                                "subscriber": {},
-                                "publisher": {},
-                                "caller": {},
-                                "callee": {},
-                            },
-                      });
-
-        let message = wdata!([
-                            WAMP_HELLO,
-                            (self.info.realm.clone()),
-                            details
-                        ]);
-        self.message_send(message).await;
+    fn new(config:ClientConfig, tracker:Tracker, runtime:Arc<dyn Runtime>) -> WampClient {
+        WampClient {
+            config: Arc::new(config),
+            tracker: Arc::new(Mutex::new(tracker)),
+            runtime,
+        }
+    }
+
+    /// Runs `future` on the client's runtime so it can await router
+    /// responses without holding up message processing
+    pub fn spawn<F:Future<Output = ()> + Send + 'static>(&self, future:F) {
+        self.runtime.spawn(Box::pin(future));
+    }
+
+    pub async fn authenticate(&mut self) -> Result<(), WampError> {
+        let mut details = WampHash::new();
+        details.insert("agent".to_string(), Box::new(self.config.agent.clone().into()));
+        details.insert("roles".to_string(), Box::new(self.config.roles_details()));
+
+        let authenticators = &self.config.authenticators;
+        if !authenticators.is_empty() {
+            let authmethods = authenticators.iter()
+                                .map(|a| WampData::from(a.authmethod()))
+                                .collect();
+            details.insert("authmethods".to_string(), Box::new(WampData::Array(Box::new(authmethods), 0)));
+        }
+        if let Some(authid) = authenticators.iter().find_map(|a| a.authid()) {
+            details.insert("authid".to_string(), Box::new(authid.into()));
+        }
+
+        self.message_send(WampMessage::Hello {
+            realm: self.config.realm.clone(),
+            details,
+        }).await
     }
 
     pub async fn next_request_id(&self) -> u64 {
         return self.tracker.lock().await.next_request_id()
     }
 
-    pub async fn request_response(&self) -> ( u64, Receiver<Box<WampData>> )  {
+    pub async fn request_response(&self) -> ( u64, Receiver<WampMessage> )  {
         let request_id = self.next_request_id().await;
-        let (s, r):(Sender<Box<WampData>>, Receiver<Box<WampData>>) = unbounded();
+        let (s, r):(Sender<WampMessage>, Receiver<WampMessage>) = unbounded();
         self.tracker.lock().await.requests_pending.insert(request_id, s);
         ( request_id, r )
     }
 
-    pub async fn submit_response(&self, message:Box<WampData>) -> Result<(), WampError> {
-        let request_id = message.a(1)?.as_u64()?;
-        match self.tracker.lock().await.requests_pending.remove(&request_id) {
-            Some(sender) => {
-                sender.send(message).await;
-                Ok(())
-            },
+    pub async fn submit_response(&self, request_id:u64, message:WampMessage) -> Result<(), WampError> {
+        // Progressive results keep the request open for what follows
+        let progress = matches!(message, WampMessage::Result { ref details, .. } if handlers::is_progress(details));
+        let sender = {
+            let mut tracker = self.tracker.lock().await;
+            if progress {
+                tracker.requests_pending.get(&request_id).cloned()
+            }
+            else {
+                tracker.requests_pending.remove(&request_id)
+            }
+        };
+        match sender {
+            // Whoever made the request may have stopped waiting for it
+            Some(sender) => sender.send(message).await.map_err(|_| WampError::UnknownRequestID),
             None => { Err(WampError::UnknownRequestID) }
         }
     }
 
-    pub async fn submit_error(&self, message:Box<WampData>) -> Result<(), WampError> {
-        let request_id = message.a(2)?.as_u64()?;
-        println!("Returning error to request_id> {}", request_id);
-        match self.tracker.lock().await.requests_pending.get(&request_id) {
-            Some(sender) => {
-                sender.send(message).await;
-                Ok(())
-            },
+    pub async fn submit_error(&self, request_id:u64, message:WampMessage) -> Result<(), WampError> {
+        let sender = self.tracker.lock().await.requests_pending.remove(&request_id);
+        match sender {
+            Some(sender) => sender.send(message).await.map_err(|_| WampError::UnknownRequestID),
             None => { Err(WampError::UnknownRequestID) }
         }
     }
 
-    pub async fn handle_challenge(&mut self, message:Box<WampData>) {
-        self.message_send(wdata!([
-                            WAMP_AUTHENTICATE,
-                            (self.info.password.clone()),
-                            {}
-                        ])).await;
+    pub async fn handle_challenge(&mut self, authmethod:String, extra:WampHash) -> Result<(), WampError> {
+        let authenticator = self.config.authenticators.iter()
+                                .find(|a| a.authmethod() == authmethod)
+                                .cloned();
+
+        match authenticator {
+            Some(authenticator) => {
+                let (signature, extra) = authenticator.challenge(&WampData::Hash(Box::new(extra), 0))?;
+                self.message_send(WampMessage::Authenticate { signature, extra }).await
+            },
+            None => {
+                println!("No authenticator for method {}", authmethod);
+                self.message_send(WampMessage::Abort {
+                    details: WampHash::new(),
+                    reason: "wamp.error.cannot_authenticate".to_string(),
+                }).await?;
+                Err(WampError::AuthenticationFailure)
+            }
+        }
     }
 
-    pub async fn handle_welcome(&mut self, message:Box<WampData>) {
-        // println!("GOT WELCOME: {:?}", message);
-        let onjoin = self.tracker.lock().await.onjoin;
+    pub async fn handle_welcome(&mut self, session:SessionDetails) -> Result<(), WampError> {
+        self.tracker.lock().await.session = Some(session.clone());
+
+        // After a reconnect the router has forgotten about everything we
+        // had set up so it needs to be redone under the new session
+        let (subscriptions, registrations, testaments) = {
+            let mut tracker = self.tracker.lock().await;
+            let subscriptions:Vec<Subscription> = tracker.subscriptions.drain().map(|(_, s)| s).collect();
+            let registrations:Vec<Registration> = tracker.registrations.drain().map(|(_, r)| r).collect();
+            (subscriptions, registrations, tracker.testaments.clone())
+        };
+        if !subscriptions.is_empty() || !registrations.is_empty() || !testaments.is_empty() {
+            let mut client = self.clone();
+            self.spawn(async move {
+                for testament in testaments {
+                    if let Err(e) = client.install_testament(&testament).await {
+                        println!("Could not reinstall testament for {}: {:?}", testament.topic, e);
+                    }
+                }
+                for subscription in subscriptions {
+                    let history = subscription.options.history;
+                    let subscription_id = match client.subscription_send(&subscription.topic, &subscription.options).await {
+                        Ok(subscription_id) => subscription_id,
+                        Err(e) => {
+                            println!("Could not resubscribe to {}: {:?}", subscription.topic, e);
+                            continue;
+                        }
+                    };
+                    client.subscription_add(subscription_id, &subscription.topic, &subscription.options, subscription.handlers.clone(), subscription.last_publication).await;
+                    if let Some(limit) = history {
+                        if let Err(e) = client.replay_history(subscription_id, &subscription, limit).await {
+                            println!("Could not replay history of {}: {:?}", subscription.topic, e);
+                        }
+                    }
+                }
+                for registration in registrations {
+                    if let Err(e) = client.register_handler(&registration.procedure, registration.options, registration.handler).await {
+                        println!("Could not reregister {}: {:?}", registration.procedure, e);
+                    }
+                }
+            });
+        }
+
+        let onjoin = self.tracker.lock().await.onjoin.clone();
         if let Some(onjoin) = onjoin {
-            onjoin(&mut self.clone(), message);
+            self.spawn(onjoin(self.clone(), session));
         }
+        Ok(())
     }
 
-    pub async fn message_send(&mut self, message:WampData) {
-        println!("Sending: {:?}", message);
-        self.transport.message_send(message.to_vec()).await;
+    pub async fn handle_event(&mut self, mut event:Event) -> Result<(), WampError> {
+
+        // Payloads the codec can't open never reach the handler
+        match self.ppt_decode(&event.details, mem::take(&mut event.args), mem::take(&mut event.kwargs)) {
+            Ok((args, kwargs)) => {
+                event.args = args;
+                event.kwargs = kwargs;
+            },
+            Err(e) => {
+                println!("Dropping event {}: {:?}", event.publication_id, e);
+                return Ok(());
+            }
+        }
+
+        let subscription = self.tracker.lock().await.subscriptions.get_mut(&event.subscription_id).map(|subscription| {
+            subscription.last_publication = Some(event.publication_id);
+            subscription.clone()
+        });
+        match subscription {
+            Some(subscription) => {
+                // Pattern subscriptions get the concrete topic in details
+                if event.topic.is_empty() {
+                    event.topic = subscription.topic.clone();
+                }
+                for handler in subscription.handlers.iter() {
+                    self.spawn(handler(self.clone(), event.clone()));
+                }
+                Ok(())
+            },
+            None => Err(WampError::UnknownRequestID),
+        }
+    }
+
+    /// Hands `subscription`'s handler the events it missed while we
+    /// were disconnected, oldest first
+    async fn replay_history(&mut self, subscription_id:u64, subscription:&Subscription, limit:u64) -> Result<(), WampError> {
+        let mut events = self.subscription_history(subscription_id, limit).await?;
+
+        // Everything up to the last event we saw was already handled
+        if let Some(last) = subscription.last_publication {
+            if let Some(i) = events.iter().position(|event| event.publication_id == last) {
+                events.drain(..=i);
+            }
+        }
+
+        // Each replayed event counts as seen so a second reconnect doesn't
+        // replay it again. Live events that arrive meanwhile win.
+        let mut seen = subscription.last_publication;
+        for mut event in events {
+            {
+                let mut tracker = self.tracker.lock().await;
+                if let Some(current) = tracker.subscriptions.get_mut(&subscription_id) {
+                    if current.last_publication == seen {
+                        current.last_publication = Some(event.publication_id);
+                        seen = current.last_publication;
+                    }
+                }
+            }
+            match self.ppt_decode(&event.details, mem::take(&mut event.args), mem::take(&mut event.kwargs)) {
+                Ok((args, kwargs)) => {
+                    event.args = args;
+                    event.kwargs = kwargs;
+                },
+                Err(e) => {
+                    println!("Dropping event {}: {:?}", event.publication_id, e);
+                    continue;
+                }
+            }
+            if event.topic.is_empty() {
+                event.topic = subscription.topic.clone();
+            }
+            for handler in subscription.handlers.iter() {
+                handler(self.clone(), event.clone()).await;
+            }
+        }
+        Ok(())
     }
 
-    pub async fn message_process(&mut self, message_str:Vec<u8>) {
-        // FIXME: Need to handle error properly
-        println!("Parsiing data");
-        let message = WampData::from_slice(message_str).unwrap();
-        println!("Parsed Data");
-        println!("Getting message type");
-        let message_type = message.a(0).unwrap().as_u64().unwrap();
-        println!("Got message type");
-        match message_type {
-            WAMP_CHALLENGE => {
-                println!("authentication request");
-                self.handle_challenge(message).await;
-                println!("authentication done");
+    pub async fn handle_invocation(&mut self, mut invocation:Invocation) -> Result<(), WampError> {
+        let request_id = invocation.request_id;
+        let registration = self.tracker.lock().await.registrations.get(&invocation.registration_id).cloned();
+        let registration = match registration {
+            Some(registration) => registration,
+            None => {
+                let error = RpcError::new("wamp.error.no_such_registration");
+                return self.message_send(error.into_message(request_id)).await;
+            }
+        };
+
+        // Pattern registrations get the concrete procedure in details
+        if invocation.procedure.is_empty() {
+            invocation.procedure = registration.procedure.clone();
+            invocation.info.procedure = registration.procedure.clone();
+        }
+        invocation.invoke = registration.options.invoke;
+
+        match self.ppt_decode(&invocation.details, mem::take(&mut invocation.args), mem::take(&mut invocation.kwargs)) {
+            Ok((args, kwargs)) => {
+                invocation.args = args;
+                invocation.kwargs = kwargs;
             },
-            WAMP_WELCOME => {
-                println!("welcome");
-                self.handle_welcome(message).await;
-                println!("welcome done");
+            Err(e) => {
+                println!("Rejecting invocation {}: {:?}", request_id, e);
+                let error = RpcError::new("wamp.error.invalid_argument");
+                return self.message_send(error.into_message(request_id)).await;
+            }
+        }
+
+        // Later chunks of a progressive call go to the handler that is
+        // already running for it
+        {
+            let mut tracker = self.tracker.lock().await;
+            if let Some(chunks) = tracker.invocations_pending.get(&request_id).cloned() {
+                if !invocation.is_progress() {
+                    tracker.invocations_pending.remove(&request_id);
+                }
+                drop(tracker);
+                return chunks.send(invocation).await.map_err(|_| WampError::UnknownRequestID);
+            }
+            if invocation.is_progress() {
+                let (sender, receiver) = unbounded();
+                tracker.invocations_pending.insert(request_id, sender);
+                invocation.chunks = Some(receiver);
+            }
+        }
+
+        let mut client = self.clone();
+        let ppt = invocation.ppt.clone();
+        self.spawn(async move {
+            let reply = match (registration.handler)(client.clone(), invocation).await {
+                Ok(result) => {
+                    let ppt = result.ppt.or(ppt);
+                    match client.yield_message(request_id, ppt.as_ref(), false, result.args, result.kwargs) {
+                        Ok(reply) => reply,
+                        Err(e) => RpcError::new("wamp.error.invalid_argument").into_message(request_id),
+                    }
+                },
+                Err(error) => error.into_message(request_id),
+            };
+            if let Err(e) = client.message_send(reply).await {
+                println!("Could not answer invocation {}: {:?}", request_id, e);
+            }
+        });
+        Ok(())
+    }
+
+    /// Sends a progressive result for `invocation` ahead of the final
+    /// one returned by the handler
+    pub async fn yield_progress(&mut self, invocation:&Invocation, args:WampArray, kwargs:WampHash) -> Result<(), WampError> {
+        if !invocation.receive_progress() {
+            return Err(WampError::ProgressNotRequested);
+        }
+        let message = self.yield_message(invocation.request_id, invocation.ppt.as_ref(), true, args, kwargs)?;
+        self.message_send(message).await
+    }
+
+    fn yield_message(&self, request_id:u64, ppt:Option<&PptOptions>, progress:bool, args:WampArray, kwargs:WampHash) -> Result<WampMessage, WampError> {
+        let mut options = WampHash::new();
+        if progress {
+            options.insert("progress".to_string(), Box::new(WampData::Bool(true)));
+        }
+        if let Some(ppt) = ppt {
+            ppt.apply(&mut options);
+        }
+        let (args, kwargs) = self.ppt_encode(ppt, args, kwargs)?;
+        Ok(WampMessage::Yield { request_id, options, args, kwargs })
+    }
+
+    pub async fn message_send(&mut self, message:WampMessage) -> Result<(), WampError> {
+        let frame = Frame::Message(self.config.serializer.encode(&message.into_data()));
+        self.frame_send(frame).await
+    }
+
+    async fn frame_send(&self, frame:Frame) -> Result<(), WampError> {
+        let outgoing = self.tracker.lock().await.outgoing.clone();
+        match outgoing {
+            Some(outgoing) => {
+                match outgoing.send(frame).await {
+                    Ok(_) => Ok(()),
+                    Err(_) => Err(WampError::NotConnected),
+                }
+            },
+            None => Err(WampError::NotConnected),
+        }
+    }
+
+    /// Handles one message from the router. Errors that end the session
+    /// are returned, anything else is logged and the message dropped.
+    pub async fn message_process(&mut self, message_str:Vec<u8>) -> Result<(), WampError> {
+        let message = self.config.serializer.decode(message_str)
+                        .and_then(|message| WampMessage::from_data(&message));
+        let message = match message {
+            Ok(message) => message,
+            Err(e) => {
+                self.invalid_message(e).await;
+                return Ok(());
+            }
+        };
+        let name = message.name();
+        let result = match message {
+            WampMessage::Challenge { authmethod, extra } => {
+                // Without an answer to the challenge there is no session
+                return self.handle_challenge(authmethod, extra).await;
+            },
+            WampMessage::Welcome { session_id, details } => {
+                self.handle_welcome(SessionDetails::new(session_id, details)).await
+            },
+            WampMessage::Abort { reason, .. } => {
+                return Err(WampError::Aborted(reason));
+            },
+            WampMessage::Goodbye { reason, .. } => {
+                println!("Router closed the session: {}", reason);
+                let result = self.message_send(WampMessage::Goodbye {
+                    details: WampHash::new(),
+                    reason: "wamp.close.goodbye_and_out".to_string(),
+                }).await;
+                // The connection ends once our GOODBYE has been written
+                self.tracker.lock().await.outgoing = None;
+                result
+            },
+            WampMessage::Result { request_id, .. }
+            | WampMessage::Subscribed { request_id, .. }
+            | WampMessage::Registered { request_id, .. }
+            | WampMessage::Published { request_id, .. } => {
+                self.submit_response(request_id, message).await
+            },
+            WampMessage::Event { subscription_id, publication_id, details, args, kwargs } => {
+                self.handle_event(Event::new(subscription_id, publication_id, details, args, kwargs)).await
             },
-            WAMP_RESULTS => {
-                println!("result!");
-                self.submit_response(message).await;
+            WampMessage::Invocation { request_id, registration_id, details, args, kwargs } => {
+                self.handle_invocation(Invocation::new(request_id, registration_id, details, args, kwargs)).await
             },
-            WAMP_ERROR => {
-                println!("ERROR!");
-                self.submit_error(message).await;
+            WampMessage::Error { request_id, .. } => {
+                self.submit_error(request_id, message).await
             },
+            // INTERRUPT included: we don't announce call_canceling as a
+            // callee so the dealer has no business sending it
             _ => {
-                println!("random MESSAGE TYPE: {:?}", message_type);
-                println!("random MESSAGE {:?}", message);
+                println!("Dropping unexpected {} message", name);
+                Ok(())
             },
         };
-        println!("<<< Leaving process");
+        if let Err(e) = result {
+            println!("Dropping {} message: {:?}", name, e);
+        }
+        Ok(())
     }
 
-    pub async fn connect(url:&str, realm:&str, username:&str, password:&str) -> WampClient {
-        let info = ConnectionInfo {
-                        url: url.to_string(),
-                        realm: realm.to_string(),
-                        username: username.to_string(),
-                        password: password.to_string(),
-                    };
-        let transport = transport::Transport::connect(url).unwrap();
-        let mut tracker = TrackerBuilder::default().build().unwrap();
+    /// Deals with a frame we couldn't make sense of according to the
+    /// configured InvalidMessagePolicy
+    async fn invalid_message(&mut self, error:WampError) {
+        let reason = match error {
+            WampError::DecodeError(ref e) => e.to_string(),
+            WampError::MalformedMessage(ref e) => e.clone(),
+            ref e => format!("{:?}", e),
+        };
+        match self.config.invalid_messages {
+            InvalidMessagePolicy::Drop => {
+                println!("Dropping invalid message: {}", reason);
+            },
+            InvalidMessagePolicy::Abort => {
+                println!("Aborting on invalid message: {}", reason);
+                let mut details = WampHash::new();
+                details.insert("message".to_string(), Box::new(reason.into()));
+                if let Err(e) = self.message_send(WampMessage::Abort {
+                    details,
+                    reason: "wamp.error.protocol_violation".to_string(),
+                }).await {
+                    println!("Could not send ABORT: {:?}", e);
+                }
+                // Closing the outgoing queue ends the connection once the
+                // ABORT has been written
+                self.tracker.lock().await.outgoing = None;
+            },
+        }
+    }
 
-        let (sender, receiver):(Sender<Vec<u8>>, Receiver<Vec<u8>>) = unbounded();
-        tracker.message_sender = Some(sender);
-        tracker.message_receiver = Some(receiver);
+    async fn open_transport(&self) -> Result<transport::Transport, WampError> {
+        let url = match self.config.url {
+            Some(ref url) => url,
+            None => return Err(WampError::NotConnected),
+        };
+        let runtime = self.runtime.as_ref();
+        runtime::with_timeout(
+            runtime,
+            self.config.connect_timeout,
+            transport::Transport::connect(runtime, url, self.config.serializer)
+        ).await
+    }
 
-        let mut wamp = WampClient {
-            info: Arc::new(info),
-            transport,
-            tracker: Arc::new(Mutex::new(tracker)),
-            //thread_stack_size: 65535,
-            //thread_stack_size: 35535,
-            //thread_stack_size: 20000,
-            thread_stack_size: 8000,
+    /// Opens the connection to the router and sends HELLO. A transport
+    /// handed to the builder is used for the first connection. Nothing
+    /// is read or written until run() is driving the connection.
+    pub async fn connect(&mut self) -> Result<(), WampError> {
+        let transport = self.tracker.lock().await.transport.take();
+        let transport = match transport {
+            Some(transport) => transport,
+            None => self.open_transport().await?,
         };
 
-        wamp.authenticate().await;
+        {
+            let (sender, receiver) = unbounded();
+            let mut tracker = self.tracker.lock().await;
+            tracker.transport = Some(transport);
+            tracker.outgoing = Some(sender);
+            tracker.outgoing_receiver = Some(receiver);
+        }
 
-        wamp
+        self.authenticate().await
     }
 
-    fn loop_process_messages(&mut self, receiver:&Receiver<Vec<u8>>) {
-        smol::block_on(async move {
+    /// Tries to bring the connection back according to the reconnect policy
+    pub async fn reconnect(&mut self) -> Result<(), WampError> {
+        let policy = self.config.reconnect.clone();
+        let mut attempt = 0;
+        while policy.allows(attempt) {
+            self.runtime.sleep(policy.delay(attempt)).await;
+            println!("Reconnect attempt {}", attempt + 1);
+            match self.connect().await {
+                Ok(_) => return Ok(()),
+                Err(e) => println!("Reconnect failed: {:?}", e),
+            }
+            attempt += 1;
+        }
+        Err(WampError::ConnectionFailure)
+    }
+
+    /// Pumps frames in both directions until the connection drops
+    async fn drive(&mut self, transport:transport::Transport, outgoing:Receiver<Frame>) -> Result<(), WampError> {
+        let (mut reader, mut writer) = transport.split();
+
+        let sending = async move {
+            while let Ok(frame) = outgoing.recv().await {
+                writer.frame_send(frame).await?;
+            }
+            Err::<(), WampError>(WampError::NotConnected)
+        };
+
+        let mut client = self.clone();
+        let receiving = async move {
             loop {
-                match receiver.try_recv() {
-                    Ok(message) => {
-                        println!("loop_process_messages.Ok.message");
-                        self.message_process(message).await;
+                match reader.frame_get().await? {
+                    Frame::Message(message) => {
+                        client.message_process(message).await?;
                     },
-                    Err(e) => {
-                        smol::Timer::after(std::time::Duration::from_millis(100)).await;
-                        smol::future::yield_now().await;
+                    Frame::Ping(payload) => {
+                        client.frame_send(Frame::Pong(payload)).await?;
                     },
-                };
-            };
-        });
+                    Frame::Pong(_) => {},
+                }
+            }
+        };
+
+        futures::pin_mut!(sending, receiving);
+        match future::select(sending, receiving).await {
+            Either::Left((result, _)) => result,
+            Either::Right((result, _)) => result,
+        }
     }
 
+    /// Forgets everything tied to the connection that just dropped.
+    /// Anyone still waiting on a response sees the connection fail.
+    async fn disconnected(&mut self) {
+        let mut tracker = self.tracker.lock().await;
+        tracker.outgoing = None;
+        tracker.outgoing_receiver = None;
+        tracker.requests_pending.clear();
+        tracker.invocations_pending.clear();
+        tracker.session = None;
+    }
 
-    fn loop_incoming_dispatch(&mut self, sender:&Sender<Vec<u8>>) {
-        smol::block_on(async move {
-            loop {
-                match self.transport.message_get().await {
-                    None => {
-                        println!("Things exploded." );
-                        smol::Timer::after(std::time::Duration::from_millis(100)).await;
-                    },
-                    Some(message) => {
-                        sender.try_send(message.to_vec());
+    /// The future that drives the client. Connects if connect() hasn't
+    /// been called, then processes messages and reconnects per the
+    /// reconnect policy. It only returns once the connection is gone for
+    /// good, and can be spawned on whichever executor the caller likes.
+    pub async fn run(&mut self) -> Result<(), WampError> {
+        if self.tracker.lock().await.outgoing.is_none() {
+            self.connect().await?;
+        }
+
+        loop {
+            let connection = {
+                let mut tracker = self.tracker.lock().await;
+                (tracker.transport.take(), tracker.outgoing_receiver.take())
+            };
+            match connection {
+                (Some(transport), Some(outgoing)) => {
+                    match self.drive(transport, outgoing).await {
+                        // The router turned us away, trying again won't help
+                        Err(e @ WampError::Aborted(_)) | Err(e @ WampError::AuthenticationFailure) => {
+                            self.disconnected().await;
+                            return Err(e);
+                        },
+                        Err(e) => println!("Things exploded: {:?}", e),
+                        Ok(_) => {},
                     }
-                }
+                },
+                _ => return Err(WampError::NotConnected),
             }
-        });
+            self.disconnected().await;
+            self.reconnect().await?;
+        }
     }
 
-    pub fn additional_thread(&mut self, stack_size:usize) -> thread::JoinHandle<()> {
-        // Thread to handle the incoming events
-        smol::block_on(async {
-            let r = self.tracker.lock().await.message_receiver.as_ref().unwrap().clone();
-            let mut thread_copy = self.clone();
-            thread::Builder::new().stack_size(stack_size).spawn(move || {
-                thread_copy.loop_process_messages(&r);
-            }).unwrap()
-        })
+    /// Sets the handler run each time a session is established
+    pub async fn onjoin<F, Fut>(&self, cb:F)
+    where
+        F: Fn(WampClient, SessionDetails) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let handler:JoinHandler = Arc::new(move |client, details| Box::pin(cb(client, details)));
+        self.tracker.lock().await.onjoin = Some(handler);
     }
 
-    pub async fn run(&mut self) {
-        self.authenticate().await;
+    /// Sets the handler told about events whose args a typed handler
+    /// couldn't decode. Without one they are logged and dropped.
+    pub async fn onevent_error<F, Fut>(&self, cb:F)
+    where
+        F: Fn(WampClient, Event, WampError) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let handler:EventErrorHandler = Arc::new(move |client, event, error| Box::pin(cb(client, event, error)));
+        self.tracker.lock().await.onevent_error = Some(handler);
+    }
 
-        let j1 = self.additional_thread(self.thread_stack_size);
-        let j2 = self.additional_thread(self.thread_stack_size);
+    /// Hands an event that couldn't be decoded to the onevent_error handler
+    pub(crate) async fn event_error(&self, event:Event, error:WampError) {
+        let handler = self.tracker.lock().await.onevent_error.clone();
+        match handler {
+            Some(handler) => handler(self.clone(), event, error).await,
+            None => println!("Dropping event {} on {}: {}", event.publication_id, event.topic, error),
+        }
+    }
 
-        // Separate thread receiver for events coming in from nexushost
-        let s1 = self.tracker.lock().await.message_sender.as_ref().unwrap().clone();
-        let mut thread_copy = self.clone();
-        let handler = thread::Builder::new().spawn(move || {
-            thread_copy.loop_incoming_dispatch(&s1);
-        }).unwrap();
+    /// Waits for the router's answer to `request_id`, turning ERROR into
+    /// a WampError
+    async fn await_response(&self, receiver:Receiver<WampMessage>) -> Result<WampMessage, WampError> {
+        match receiver.recv().await {
+            Ok(WampMessage::Error { error, .. }) => Err(WampError::RouterError(error)),
+            Ok(message) => Ok(message),
+            Err(_) => Err(WampError::ConnectionFailure),
+        }
+    }
 
-        // Permablock for now
-        smol::block_on(async move {
-            loop {
-                smol::Timer::after(std::time::Duration::from_millis(10)).await;
-            }
-        });
+    /// Sends SUBSCRIBE, returning the subscription id the router picked
+    async fn subscription_send(&mut self, topic:&str, options:&SubscribeOptions) -> Result<u64, WampError> {
+        self.require_features("broker", &options.required_features()).await?;
+        let ( request_id, receiver ) = self.request_response().await;
+        self.message_send(WampMessage::Subscribe {
+            request_id,
+            options: options.to_details(),
+            topic: topic.to_string(),
+        }).await?;
+
+        match self.await_response(receiver).await? {
+            WampMessage::Subscribed { subscription_id, .. } => Ok(subscription_id),
+            other => Err(unexpected_reply("SUBSCRIBED", &other)),
+        }
+    }
+
+    /// Adds `handlers` to the subscription `subscription_id`, joining any
+    /// handlers already subscribed under the same id. `last_publication`
+    /// carries over where a subscription we had before a reconnect left off.
+    async fn subscription_add(&self, subscription_id:u64, topic:&str, options:&SubscribeOptions, handlers:Vec<EventHandler>, last_publication:Option<u64>) {
+        let mut tracker = self.tracker.lock().await;
+        let subscription = tracker.subscriptions.entry(subscription_id)
+            .or_insert_with(|| Subscription {
+                topic: topic.to_string(),
+                options: options.clone(),
+                handlers: Vec::new(),
+                last_publication: None,
+            });
+        subscription.handlers.extend(handlers);
+        if subscription.last_publication.is_none() {
+            subscription.last_publication = last_publication;
+        }
+    }
 
+    async fn subscribe_handler(&mut self, topic:&str, options:SubscribeOptions, handler:EventHandler) -> Result<u64, WampError> {
+        let subscription_id = self.subscription_send(topic, &options).await?;
+        self.subscription_add(subscription_id, topic, &options, vec![handler], None).await;
+        Ok(subscription_id)
     }
 
-    pub fn onjoin (&self, cb:JoinFn) {
-        smol::block_on(async {
-            self.tracker.lock().await.onjoin = Some(cb);
-        });
+    /// Subscribes `cb` to `topic`, returning the subscription id
+    pub async fn subscribe<F, Fut>(&mut self, topic:&str, cb:F) -> Result<u64, WampError>
+    where
+        F: Fn(WampClient, Event) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.subscribe_with(topic, SubscribeOptions::default(), cb).await
+    }
+
+    pub async fn subscribe_with<F, Fut>(&mut self, topic:&str, options:SubscribeOptions, cb:F) -> Result<u64, WampError>
+    where
+        F: Fn(WampClient, Event) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let handler:EventHandler = Arc::new(move |client, event| Box::pin(cb(client, event)));
+        self.subscribe_handler(topic, options, handler).await
+    }
+
+    /// Details of the current session, if we have one
+    pub async fn session(&self) -> Option<SessionDetails> {
+        self.tracker.lock().await.session.clone()
     }
 
-    pub async fn call(&mut self, uri:&str, args:WampData, kwargs:WampData ) -> Box<WampData> {
+    /// Requests still waiting on an answer from the router
+    pub async fn requests_pending(&self) -> usize {
+        self.tracker.lock().await.requests_pending.len()
+    }
+
+    /// Progressive invocations still waiting on more chunks from the caller
+    pub async fn invocations_pending(&self) -> usize {
+        self.tracker.lock().await.invocations_pending.len()
+    }
+
+    /// Fails with FeatureNotSupported unless the router announced every
+    /// one of `features` for `role`. Keeps us from sending options the
+    /// router would silently ignore.
+    pub(crate) async fn require_features(&self, role:&str, features:&[&str]) -> Result<(), WampError> {
+        if features.is_empty() {
+            return Ok(());
+        }
+        let session = self.session().await.ok_or(WampError::NotConnected)?;
+        match features.iter().find(|feature| !session.router_has_feature(role, feature)) {
+            Some(feature) => Err(WampError::FeatureNotSupported(feature.to_string())),
+            None => Ok(()),
+        }
+    }
+
+    async fn register_handler(&mut self, procedure:&str, options:RegisterOptions, handler:InvocationHandler) -> Result<u64, WampError> {
+        self.require_features("dealer", &options.required_features()).await?;
+
         let ( request_id, receiver ) = self.request_response().await;
-        let message:WampData = wdata!([
-                                    WAMP_CALL,
-                                    request_id, // request id
-                                    {}, // options
-                                    uri, // procedure
-                                    // args,
-                                    // kwargs
-                                ]);
-
-        println!("Message: {:?}", message);
-        self.message_send(message).await;
+        self.message_send(WampMessage::Register {
+            request_id,
+            options: options.to_details(),
+            procedure: procedure.to_string(),
+        }).await?;
+
+        let registration_id = match self.await_response(receiver).await? {
+            WampMessage::Registered { registration_id, .. } => registration_id,
+            other => return Err(unexpected_reply("REGISTERED", &other)),
+        };
 
-        loop {
-            match receiver.try_recv() {
-                Ok(t) => {
-                    return t
-                },
-                Err(e) => {
-                    smol::Timer::after(std::time::Duration::from_millis(100)).await;
-                    smol::future::yield_now().await;
-                },
-            };
+        // Only one handler can answer an invocation, so the first one
+        // registered under an id keeps it
+        let mut tracker = self.tracker.lock().await;
+        if tracker.registrations.contains_key(&registration_id) {
+            return Err(WampError::AlreadyRegistered(procedure.to_string()));
         }
+        tracker.registrations.insert(registration_id, Registration {
+            procedure: procedure.to_string(),
+            options,
+            handler,
+        });
+        Ok(registration_id)
     }
-}
 
+    /// Registers `cb` as the callee for `procedure`, returning the
+    /// registration id
+    pub async fn register<F, Fut>(&mut self, procedure:&str, cb:F) -> Result<u64, WampError>
+    where
+        F: Fn(WampClient, Invocation) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Yield, RpcError>> + Send + 'static,
+    {
+        self.register_with(procedure, RegisterOptions::default(), cb).await
+    }
 
+    pub async fn register_with<F, Fut>(&mut self, procedure:&str, options:RegisterOptions, cb:F) -> Result<u64, WampError>
+    where
+        F: Fn(WampClient, Invocation) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Yield, RpcError>> + Send + 'static,
+    {
+        let handler:InvocationHandler = Arc::new(move |client, invocation| Box::pin(cb(client, invocation)));
+        self.register_handler(procedure, options, handler).await
+    }
 
+    pub async fn publish(&mut self, topic:&str, args:WampData, kwargs:WampData) -> Result<Option<u64>, WampError> {
+        self.publish_with(topic, args, kwargs, PublishOptions::default()).await
+    }
+
+    /// Publishes with advanced options. Returns the publication id when
+    /// `acknowledge` is set.
+    pub async fn publish_with(&mut self, topic:&str, args:WampData, kwargs:WampData, options:PublishOptions) -> Result<Option<u64>, WampError> {
+        self.require_features("broker", &options.required_features()).await?;
+        let (args, kwargs) = self.ppt_encode(options.ppt.as_ref(), args_from(args), kwargs_from(kwargs)?)?;
+        let message = |request_id:u64| WampMessage::Publish {
+            request_id,
+            options: options.to_details(),
+            topic: topic.to_string(),
+            args: args.clone(),
+            kwargs: kwargs.clone(),
+        };
+
+        if !options.acknowledge {
+            let request_id = self.next_request_id().await;
+            self.message_send(message(request_id)).await?;
+            return Ok(None);
+        }
+
+        let ( request_id, receiver ) = self.request_response().await;
+        self.message_send(message(request_id)).await?;
+        match self.await_response(receiver).await? {
+            WampMessage::Published { publication_id, .. } => Ok(Some(publication_id)),
+            other => Err(unexpected_reply("PUBLISHED", &other)),
+        }
+    }
+}
+
+/// Error for a router reply that isn't the one the request expects
+pub(crate) fn unexpected_reply(expected:&str, message:&WampMessage) -> WampError {
+    WampError::MalformedMessage(format!("expected {} but got {}", expected, message.name()))
+}
@@ -0,0 +1,51 @@
+use crate::serialization::WampData;
+use crate::{WampError, WampHash};
+
+/*
+ * Something that can answer a router's CHALLENGE
+ */
+pub trait Authenticator: Send + Sync {
+    /// Method name announced in HELLO's `authmethods`
+    fn authmethod(&self) -> &str;
+
+    /// Identity announced in HELLO's `authid`
+    fn authid(&self) -> Option<&str> {
+        None
+    }
+
+    /// Produces the `[signature, extra]` pair for the AUTHENTICATE
+    /// message in response to a CHALLENGE's `extra` details
+    fn challenge(&self, extra:&WampData) -> Result<(String, WampHash), WampError>;
+}
+
+/*
+ * WAMP-Ticket authentication
+ */
+#[derive(Debug, Clone)]
+pub struct TicketAuthenticator {
+    authid: String,
+    ticket: String,
+}
+
+impl TicketAuthenticator {
+    pub fn new(authid:&str, ticket:&str) -> TicketAuthenticator {
+        TicketAuthenticator {
+            authid: authid.to_string(),
+            ticket: ticket.to_string(),
+        }
+    }
+}
+
+impl Authenticator for TicketAuthenticator {
+    fn authmethod(&self) -> &str {
+        "ticket"
+    }
+
+    fn authid(&self) -> Option<&str> {
+        Some(&self.authid)
+    }
+
+    fn challenge(&self, extra:&WampData) -> Result<(String, WampHash), WampError> {
+        Ok((self.ticket.clone(), WampHash::new()))
+    }
+}
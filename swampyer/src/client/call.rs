@@ -0,0 +1,360 @@
+use std::time::Duration;
+
+// To help us build Builders
+use derive_builder::Builder;
+
+use async_channel::Receiver;
+use futures::stream::{self, Stream};
+
+use crate::serialization::WampData;
+use crate::message::{WampMessage, args_from, kwargs_from};
+use crate::{WampError, WampHash, WampArray};
+use super::handlers::{is_progress, RpcError};
+use super::payload::PptOptions;
+use super::runtime;
+use super::{WampClient, unexpected_reply};
+
+/*
+ * How the dealer should treat a call we CANCEL
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CancelMode {
+    /// Stop waiting but let the callee finish
+    Skip,
+    /// Interrupt the callee and wait for it to acknowledge
+    Kill,
+    /// Interrupt the callee without waiting for it
+    #[default]
+    KillNoWait,
+}
+
+impl CancelMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CancelMode::Skip => "skip",
+            CancelMode::Kill => "kill",
+            CancelMode::KillNoWait => "killnowait",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Builder)]
+#[builder(setter(into), build_fn(error = "WampError"))]
+pub struct CallOptions {
+    /// How long we wait for the RESULT before giving up. Falls back to
+    /// the client's call_timeout.
+    #[builder(setter(strip_option), default)]
+    pub timeout: Option<Duration>,
+
+    /// Sent as the CALL `timeout` option so the dealer cancels the call
+    /// on our behalf
+    #[builder(setter(strip_option), default)]
+    pub router_timeout: Option<Duration>,
+
+    /// Mode used for the CANCEL sent on timeout or when the call is dropped
+    #[builder(default)]
+    pub cancel_mode: CancelMode,
+
+    /// Ask the callee for progressive results. Read them through
+    /// PendingCall::into_stream.
+    #[builder(default)]
+    pub receive_progress: bool,
+
+    /// Start a progressive invocation. More input is sent with
+    /// PendingCall::send_progress and closed off with send_final.
+    #[builder(default)]
+    pub progress: bool,
+
+    /// Ask the dealer to tell the callee who we are
+    #[builder(default)]
+    pub disclose_me: bool,
+
+    /// Send args and kwargs in Payload PassThru Mode
+    #[builder(setter(strip_option), default)]
+    pub ppt: Option<PptOptions>,
+}
+
+impl CallOptions {
+    pub fn to_details(&self) -> WampHash {
+        let mut options = WampHash::new();
+        if let Some(timeout) = self.router_timeout {
+            options.insert("timeout".to_string(), Box::new(WampData::UInt(timeout.as_millis() as u64)));
+        }
+        if self.receive_progress {
+            options.insert("receive_progress".to_string(), Box::new(WampData::Bool(true)));
+        }
+        if self.progress {
+            options.insert("progress".to_string(), Box::new(WampData::Bool(true)));
+        }
+        if self.disclose_me {
+            options.insert("disclose_me".to_string(), Box::new(WampData::Bool(true)));
+        }
+        if let Some(ref ppt) = self.ppt {
+            ppt.apply(&mut options);
+        }
+        options
+    }
+
+    /// Dealer features these options depend on
+    pub(crate) fn required_features(&self) -> Vec<&'static str> {
+        let mut features = Vec::new();
+        if self.router_timeout.is_some() {
+            features.push("call_timeout");
+        }
+        if self.receive_progress {
+            features.push("progressive_call_results");
+        }
+        if self.progress {
+            features.push("progressive_call_invocations");
+        }
+        if self.disclose_me {
+            features.push("caller_identification");
+        }
+        if self.ppt.is_some() {
+            features.push("payload_passthru_mode");
+        }
+        features
+    }
+}
+
+/*
+ * RESULT of a call
+ */
+#[derive(Debug, Clone)]
+pub struct CallResult {
+    /// Set when the callee answered in Payload PassThru Mode
+    pub ppt: Option<PptOptions>,
+    pub details: WampHash,
+    pub args: WampArray,
+    pub kwargs: WampHash,
+}
+
+impl CallResult {
+    /// True for the intermediate results of a progressive call
+    pub fn is_progress(&self) -> bool {
+        is_progress(&self.details)
+    }
+
+    pub(crate) fn new(details:WampHash, args:WampArray, kwargs:WampHash) -> CallResult {
+        CallResult {
+            ppt: PptOptions::from_details(&details),
+            details,
+            args,
+            kwargs,
+        }
+    }
+}
+
+/*
+ * Lets another task CANCEL a call that is in flight
+ */
+#[derive(Clone)]
+pub struct CallCanceller {
+    client: WampClient,
+    request_id: u64,
+}
+
+impl CallCanceller {
+    pub async fn cancel(&self, mode:CancelMode) -> Result<(), WampError> {
+        self.client.clone().cancel_request(self.request_id, mode).await
+    }
+}
+
+/*
+ * A CALL that has been sent but not answered yet. Dropping it before
+ * the answer arrives sends CANCEL.
+ */
+pub struct PendingCall {
+    client: WampClient,
+    request_id: u64,
+    uri: String,
+    options: CallOptions,
+    receiver: Receiver<WampMessage>,
+    timeout: Option<Duration>,
+    cancel_mode: CancelMode,
+    done: bool,
+}
+
+impl PendingCall {
+    pub fn request_id(&self) -> u64 {
+        self.request_id
+    }
+
+    pub fn canceller(&self) -> CallCanceller {
+        CallCanceller {
+            client: self.client.clone(),
+            request_id: self.request_id,
+        }
+    }
+
+    pub async fn cancel(&self, mode:CancelMode) -> Result<(), WampError> {
+        self.canceller().cancel(mode).await
+    }
+
+    async fn send_chunk(&mut self, args:WampData, kwargs:WampData, progress:bool) -> Result<(), WampError> {
+        if !self.options.progress {
+            return Err(WampError::ProgressNotRequested);
+        }
+        let options = CallOptions { progress, ..self.options.clone() };
+        let (args, kwargs) = self.client.ppt_encode(options.ppt.as_ref(), args_from(args), kwargs_from(kwargs)?)?;
+        self.client.message_send(WampMessage::Call {
+            request_id: self.request_id,
+            options: options.to_details(),
+            procedure: self.uri.clone(),
+            args,
+            kwargs,
+        }).await
+    }
+
+    /// Sends another chunk of input for a progressive invocation
+    pub async fn send_progress(&mut self, args:WampData, kwargs:WampData) -> Result<(), WampError> {
+        self.send_chunk(args, kwargs, true).await
+    }
+
+    /// Sends the last chunk of input for a progressive invocation
+    pub async fn send_final(&mut self, args:WampData, kwargs:WampData) -> Result<(), WampError> {
+        self.send_chunk(args, kwargs, false).await?;
+        self.options.progress = false;
+        Ok(())
+    }
+
+    /// Waits for the outcome of the call. Progressive results are
+    /// skipped over.
+    pub async fn result(mut self) -> Result<CallResult, WampError> {
+        loop {
+            let result = self.next_result().await?;
+            if !result.is_progress() {
+                return Ok(result);
+            }
+        }
+    }
+
+    /// Progressive results as they arrive followed by the final result
+    pub fn into_stream(self) -> impl Stream<Item = Result<CallResult, WampError>> {
+        stream::unfold((self, false), |(mut call, finished)| async move {
+            if finished {
+                return None;
+            }
+            match call.next_result().await {
+                Ok(result) => {
+                    let finished = !result.is_progress();
+                    Some((Ok(result), (call, finished)))
+                },
+                Err(e) => Some((Err(e), (call, true))),
+            }
+        })
+    }
+
+    /// Waits for the next RESULT, which may be a progressive one. The
+    /// timeout applies to each wait.
+    async fn next_result(&mut self) -> Result<CallResult, WampError> {
+        let receiver = self.receiver.clone();
+        let result = runtime::with_timeout(
+            self.client.runtime.as_ref(),
+            self.timeout,
+            async {
+                match receiver.recv().await {
+                    Ok(message) => Ok(message),
+                    Err(_) => Err(WampError::ConnectionFailure),
+                }
+            }
+        ).await;
+
+        let message = match result {
+            Ok(message) => message,
+            // Leave `done` unset so drop sends the CANCEL
+            Err(WampError::Timeout) => return Err(WampError::Timeout),
+            Err(e) => {
+                self.done = true;
+                return Err(e);
+            },
+        };
+
+        let mut result = match message {
+            WampMessage::Result { details, args, kwargs, .. } => CallResult::new(details, args, kwargs),
+            WampMessage::Error { error, args, kwargs, .. } => {
+                self.done = true;
+                if error == "wamp.error.canceled" {
+                    return Err(WampError::Cancelled);
+                }
+                return Err(WampError::CallFailed(RpcError { error, args, kwargs }));
+            },
+            other => {
+                self.done = true;
+                return Err(unexpected_reply("RESULT", &other));
+            },
+        };
+        if !result.is_progress() {
+            self.done = true;
+        }
+        let (args, kwargs) = self.client.ppt_decode(&result.details, result.args, result.kwargs)?;
+        result.args = args;
+        result.kwargs = kwargs;
+        Ok(result)
+    }
+}
+
+impl Drop for PendingCall {
+    fn drop(&mut self) {
+        if self.done {
+            return;
+        }
+        let mut client = self.client.clone();
+        let request_id = self.request_id;
+        let mode = self.cancel_mode;
+        self.client.spawn(async move {
+            client.cancel_request(request_id, mode).await;
+            client.tracker.lock().await.requests_pending.remove(&request_id);
+        });
+    }
+}
+
+impl WampClient {
+
+    /// Sends CANCEL for an outstanding call
+    pub async fn cancel_request(&mut self, request_id:u64, mode:CancelMode) -> Result<(), WampError> {
+        self.require_features("dealer", &["call_canceling"]).await?;
+        let mut options = WampHash::new();
+        options.insert("mode".to_string(), Box::new(mode.as_str().into()));
+        self.message_send(WampMessage::Cancel { request_id, options }).await
+    }
+
+    /// Sends the CALL and hands back the pending call so it can be
+    /// cancelled or awaited
+    pub async fn start_call(&mut self, uri:&str, args:WampData, kwargs:WampData, options:CallOptions) -> Result<PendingCall, WampError> {
+        self.require_features("dealer", &options.required_features()).await?;
+        let (args, kwargs) = self.ppt_encode(options.ppt.as_ref(), args_from(args), kwargs_from(kwargs)?)?;
+        let ( request_id, receiver ) = self.request_response().await;
+        let message = WampMessage::Call {
+            request_id,
+            options: options.to_details(),
+            procedure: uri.to_string(),
+            args,
+            kwargs,
+        };
+
+        if let Err(e) = self.message_send(message).await {
+            self.tracker.lock().await.requests_pending.remove(&request_id);
+            return Err(e);
+        }
+
+        Ok(PendingCall {
+            client: self.clone(),
+            request_id,
+            uri: uri.to_string(),
+            timeout: options.timeout.or(self.config.call_timeout),
+            cancel_mode: options.cancel_mode,
+            options,
+            receiver,
+            done: false,
+        })
+    }
+
+    pub async fn call_with(&mut self, uri:&str, args:WampData, kwargs:WampData, options:CallOptions) -> Result<CallResult, WampError> {
+        self.start_call(uri, args, kwargs, options).await?.result().await
+    }
+
+    pub async fn call(&mut self, uri:&str, args:WampData, kwargs:WampData ) -> Result<CallResult, WampError> {
+        self.call_with(uri, args, kwargs, CallOptions::default()).await
+    }
+}
@@ -0,0 +1,287 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+// To help us build Builders
+use derive_builder::Builder;
+
+use crate::serialization::{Serializer, WampData};
+use crate::{WampError, WampHash};
+use super::transport::Transport;
+use super::auth::{Authenticator, TicketAuthenticator};
+use super::runtime::{self, Runtime};
+use super::payload::PayloadCodec;
+use super::{WampClient, TrackerBuilder};
+
+/*
+ * Client side roles that can be announced in HELLO
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Role {
+    Subscriber,
+    Publisher,
+    Caller,
+    Callee,
+}
+
+impl Role {
+    pub fn all() -> Vec<Role> {
+        vec![ Role::Subscriber, Role::Publisher, Role::Caller, Role::Callee ]
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Subscriber => "subscriber",
+            Role::Publisher => "publisher",
+            Role::Caller => "caller",
+            Role::Callee => "callee",
+        }
+    }
+
+    /// Advanced profile features we implement for this role, announced
+    /// in HELLO unless told otherwise
+    pub fn supported_features(&self) -> &'static [&'static str] {
+        match self {
+            Role::Subscriber => &[
+                "pattern_based_subscription",
+                "publisher_identification",
+                "event_history",
+                "payload_passthru_mode",
+            ],
+            Role::Publisher => &[
+                "publisher_exclusion",
+                "publisher_identification",
+                "subscriber_blackwhite_listing",
+                "payload_passthru_mode",
+            ],
+            Role::Caller => &[
+                "progressive_call_results",
+                "progressive_call_invocations",
+                "call_canceling",
+                "call_timeout",
+                "caller_identification",
+                "payload_passthru_mode",
+            ],
+            // No call_canceling: INTERRUPT isn't acted on
+            Role::Callee => &[
+                "progressive_call_results",
+                "progressive_call_invocations",
+                "caller_identification",
+                "pattern_based_registration",
+                "shared_registration",
+                "payload_passthru_mode",
+            ],
+        }
+    }
+}
+
+fn supported_features() -> HashMap<Role, Vec<String>> {
+    Role::all().into_iter()
+        .map(|role| (role, role.supported_features().iter().map(|f| f.to_string()).collect()))
+        .collect()
+}
+
+/*
+ * How hard we try to get the connection back when it drops
+ */
+#[derive(Debug, Clone)]
+pub struct ReconnectPolicy {
+    /// `None` keeps trying forever
+    pub max_attempts: Option<u32>,
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    pub multiplier: f64,
+}
+
+impl ReconnectPolicy {
+    pub fn never() -> ReconnectPolicy {
+        ReconnectPolicy {
+            max_attempts: Some(0),
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(1),
+            multiplier: 1.0,
+        }
+    }
+
+    pub fn exponential(initial_delay:Duration, max_delay:Duration) -> ReconnectPolicy {
+        ReconnectPolicy {
+            max_attempts: None,
+            initial_delay,
+            max_delay,
+            multiplier: 2.0,
+        }
+    }
+
+    pub fn allows(&self, attempt:u32) -> bool {
+        match self.max_attempts {
+            Some(max) => attempt < max,
+            None => true,
+        }
+    }
+
+    pub fn delay(&self, attempt:u32) -> Duration {
+        let delay = self.initial_delay.as_secs_f64() * self.multiplier.powi(attempt as i32);
+        Duration::from_secs_f64(delay.min(self.max_delay.as_secs_f64()))
+    }
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        ReconnectPolicy::never()
+    }
+}
+
+/*
+ * What to do with a frame from the router that can't be decoded or
+ * isn't a valid WAMP message
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InvalidMessagePolicy {
+    /// Log it and carry on with the session
+    #[default]
+    Drop,
+    /// ABORT the session with `wamp.error.protocol_violation`
+    Abort,
+}
+
+/*
+ * Everything needed to bring up a session. Built through
+ * WampClientBuilder which hands back an unconnected WampClient.
+ */
+#[derive(Builder)]
+#[builder(
+    name = "WampClientBuilder",
+    pattern = "owned",
+    setter(into),
+    build_fn(private, name = "build_config", error = "WampError"),
+)]
+pub struct ClientConfig {
+    /// `host:port` of the router's rawsocket endpoint
+    #[builder(setter(into, strip_option), default)]
+    pub(crate) url: Option<String>,
+
+    /// Already established transport to use for the first connection
+    #[builder(setter(strip_option), default)]
+    pub(crate) transport: Option<Transport>,
+
+    pub(crate) realm: String,
+
+    #[builder(setter(custom), default)]
+    pub(crate) authenticators: Vec<Arc<dyn Authenticator>>,
+
+    #[builder(default)]
+    pub(crate) serializer: Serializer,
+
+    /// Where handlers get spawned and timers come from. Defaults to
+    /// smol, or tokio when only that feature is enabled.
+    #[builder(setter(custom), default)]
+    pub(crate) runtime: Option<Arc<dyn Runtime>>,
+
+    #[builder(setter(strip_option), default)]
+    pub(crate) connect_timeout: Option<Duration>,
+
+    /// Default time to wait for a RESULT
+    #[builder(setter(strip_option), default)]
+    pub(crate) call_timeout: Option<Duration>,
+
+    #[builder(default)]
+    pub(crate) reconnect: ReconnectPolicy,
+
+    #[builder(default)]
+    pub(crate) invalid_messages: InvalidMessagePolicy,
+
+    #[builder(default = "Role::all()")]
+    pub(crate) roles: Vec<Role>,
+
+    /// Features announced per role. Defaults to everything we support.
+    #[builder(setter(custom), default = "supported_features()")]
+    pub(crate) features: HashMap<Role, Vec<String>>,
+
+    #[builder(default = "\"swampyer-rs\".to_string()")]
+    pub(crate) agent: String,
+
+    /// Encodes and decodes payloads sent with PptOptions
+    #[builder(setter(custom), default)]
+    pub(crate) payload_codec: Option<Arc<dyn PayloadCodec>>,
+}
+
+impl ClientConfig {
+    /// Builds the `roles` dict for HELLO with any configured features
+    pub fn roles_details(&self) -> WampData {
+        let mut roles = WampHash::new();
+        for role in self.roles.iter() {
+            let mut role_details = WampHash::new();
+            if let Some(features) = self.features.get(role) {
+                let features = features.iter()
+                                  .map(|f| (f.clone(), Box::new(WampData::Bool(true))))
+                                  .collect();
+                role_details.insert("features".to_string(), Box::new(WampData::Hash(Box::new(features), 0)));
+            }
+            roles.insert(role.as_str().to_string(), Box::new(WampData::Hash(Box::new(role_details), 0)));
+        }
+        WampData::Hash(Box::new(roles), 0)
+    }
+}
+
+impl WampClientBuilder {
+    pub fn authenticator<A: Authenticator + 'static>(mut self, authenticator:A) -> Self {
+        self.authenticators
+            .get_or_insert_with(Vec::new)
+            .push(Arc::new(authenticator));
+        self
+    }
+
+    pub fn runtime<R: Runtime + 'static>(mut self, runtime:R) -> Self {
+        self.runtime = Some(Some(Arc::new(runtime)));
+        self
+    }
+
+    pub fn payload_codec<C: PayloadCodec + 'static>(mut self, codec:C) -> Self {
+        self.payload_codec = Some(Some(Arc::new(codec)));
+        self
+    }
+
+    /// Shorthand for adding a TicketAuthenticator
+    pub fn ticket(self, authid:&str, ticket:&str) -> Self {
+        self.authenticator(TicketAuthenticator::new(authid, ticket))
+    }
+
+    /// Also announces `feature` for `role` in HELLO
+    pub fn feature(mut self, role:Role, feature:&str) -> Self {
+        let features = self.features
+                           .get_or_insert_with(supported_features)
+                           .entry(role)
+                           .or_default();
+        if !features.iter().any(|f| f == feature) {
+            features.push(feature.to_string());
+        }
+        self
+    }
+
+    /// Stops announcing `feature` for `role` in HELLO
+    pub fn without_feature(mut self, role:Role, feature:&str) -> Self {
+        if let Some(features) = self.features.get_or_insert_with(supported_features).get_mut(&role) {
+            features.retain(|f| f != feature);
+        }
+        self
+    }
+
+    /// Creates the client without connecting to the router
+    pub fn build(self) -> Result<WampClient, WampError> {
+        let mut config = self.build_config()?;
+        if config.url.is_none() && config.transport.is_none() {
+            return Err(WampError::InvalidConfiguration("url or transport must be set".to_string()));
+        }
+
+        let runtime = match config.runtime.take() {
+            Some(runtime) => runtime,
+            None => runtime::default_runtime()?,
+        };
+
+        let transport = config.transport.take();
+        let mut tracker = TrackerBuilder::default().build().unwrap();
+        tracker.transport = transport;
+
+        Ok(WampClient::new(config, tracker, runtime))
+    }
+}
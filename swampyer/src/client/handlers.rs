@@ -0,0 +1,313 @@
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use async_channel::Receiver;
+use futures::stream::{self, Stream};
+
+use crate::serialization::WampData;
+use crate::message::{WampMessage, WAMP_INVOCATION};
+use crate::{WampError, WampHash, WampArray};
+use super::WampClient;
+use super::options::InvokePolicy;
+use super::payload::PptOptions;
+
+pub type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+
+pub(crate) type JoinHandler = Arc<dyn Fn(WampClient, SessionDetails) -> BoxFuture<()> + Send + Sync>;
+pub(crate) type EventHandler = Arc<dyn Fn(WampClient, Event) -> BoxFuture<()> + Send + Sync>;
+pub(crate) type EventErrorHandler = Arc<dyn Fn(WampClient, Event, WampError) -> BoxFuture<()> + Send + Sync>;
+pub(crate) type InvocationHandler = Arc<dyn Fn(WampClient, Invocation) -> BoxFuture<Result<Yield, RpcError>> + Send + Sync>;
+
+/// String value of `key` in a details dict, empty when absent
+pub(crate) fn str_in(details:&WampHash, key:&str) -> String {
+    match details.get(key).map(|v| v.as_ref()) {
+        Some(WampData::Str(s)) => s.clone(),
+        _ => String::new(),
+    }
+}
+
+pub(crate) fn opt_str_in(details:&WampHash, key:&str) -> Option<String> {
+    match details.get(key).map(|v| v.as_ref()) {
+        Some(WampData::Str(s)) => Some(s.clone()),
+        _ => None,
+    }
+}
+
+pub(crate) fn u64_in(details:&WampHash, key:&str) -> Option<u64> {
+    details.get(key).and_then(|v| v.as_u64().ok())
+}
+
+/// True when a details/options dict carries `progress: true`
+pub(crate) fn is_progress(details:&WampHash) -> bool {
+    matches!(details.get("progress").map(|v| v.as_ref()), Some(WampData::Bool(true)))
+}
+
+/*
+ * Roles the router plays and the features it announced for each
+ */
+#[derive(Debug, Clone, Default)]
+pub struct RouterFeatures {
+    roles: HashMap<String, HashSet<String>>,
+}
+
+impl RouterFeatures {
+    /// Whether the router acts as `role` (`broker` or `dealer`)
+    pub fn has_role(&self, role:&str) -> bool {
+        self.roles.contains_key(role)
+    }
+
+    pub fn has_feature(&self, role:&str, feature:&str) -> bool {
+        self.roles.get(role).is_some_and(|features| features.contains(feature))
+    }
+
+    pub fn features(&self, role:&str) -> Vec<String> {
+        self.roles.get(role).map(|features| features.iter().cloned().collect()).unwrap_or_default()
+    }
+
+    fn from_details(details:&WampHash) -> RouterFeatures {
+        let mut roles = HashMap::new();
+        if let Some(WampData::Hash(announced, _)) = details.get("roles").map(|v| v.as_ref()) {
+            for (role, role_details) in announced.iter() {
+                let features = match role_details.h("features") {
+                    Ok(WampData::Hash(features, _)) => {
+                        features.iter()
+                                .filter(|(_, enabled)| matches!(enabled.as_ref(), WampData::Bool(true)))
+                                .map(|(feature, _)| feature.clone())
+                                .collect()
+                    },
+                    _ => HashSet::new(),
+                };
+                roles.insert(role.clone(), features);
+            }
+        }
+        RouterFeatures { roles }
+    }
+}
+
+/*
+ * Handed to the onjoin handler once the router WELCOMEs us
+ */
+#[derive(Debug, Clone)]
+pub struct SessionDetails {
+    pub session_id: u64,
+    /// Parsed from the `roles` in `details`
+    pub router: RouterFeatures,
+    pub details: WampHash,
+}
+
+impl SessionDetails {
+    /// Whether the router announced `feature` for `role` (`broker` or
+    /// `dealer`) in WELCOME
+    pub fn router_has_feature(&self, role:&str, feature:&str) -> bool {
+        self.router.has_feature(role, feature)
+    }
+
+    pub(crate) fn new(session_id:u64, details:WampHash) -> SessionDetails {
+        SessionDetails {
+            session_id,
+            router: RouterFeatures::from_details(&details),
+            details,
+        }
+    }
+}
+
+/*
+ * A publication delivered to one of our subscriptions
+ */
+#[derive(Debug, Clone)]
+pub struct Event {
+    pub subscription_id: u64,
+    pub publication_id: u64,
+    /// Topic the event was published to
+    pub topic: String,
+    /// Publisher identification, present when the publisher disclosed itself
+    pub publisher: Option<u64>,
+    pub publisher_authid: Option<String>,
+    pub publisher_authrole: Option<String>,
+    /// Set when the event was published in Payload PassThru Mode
+    pub ppt: Option<PptOptions>,
+    pub details: WampHash,
+    pub args: WampArray,
+    pub kwargs: WampHash,
+}
+
+impl Event {
+    pub(crate) fn new(subscription_id:u64, publication_id:u64, details:WampHash, args:WampArray, kwargs:WampHash) -> Event {
+        Event {
+            subscription_id,
+            publication_id,
+            topic: str_in(&details, "topic"),
+            publisher: u64_in(&details, "publisher"),
+            publisher_authid: opt_str_in(&details, "publisher_authid"),
+            publisher_authrole: opt_str_in(&details, "publisher_authrole"),
+            ppt: PptOptions::from_details(&details),
+            details,
+            args,
+            kwargs,
+        }
+    }
+}
+
+/*
+ * Who is calling and how, from INVOCATION details. The caller fields
+ * are only filled in when the caller disclosed itself.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct InvocationDetails {
+    pub caller: Option<u64>,
+    pub caller_authid: Option<String>,
+    pub caller_authrole: Option<String>,
+    /// Concrete procedure called, also for pattern registrations
+    pub procedure: String,
+    pub trustlevel: Option<u64>,
+}
+
+impl InvocationDetails {
+    pub(crate) fn from_details(details:&WampHash) -> InvocationDetails {
+        InvocationDetails {
+            caller: u64_in(details, "caller"),
+            caller_authid: opt_str_in(details, "caller_authid"),
+            caller_authrole: opt_str_in(details, "caller_authrole"),
+            procedure: str_in(details, "procedure"),
+            trustlevel: u64_in(details, "trustlevel"),
+        }
+    }
+}
+
+/*
+ * A call routed to one of our registered procedures
+ */
+#[derive(Debug, Clone)]
+pub struct Invocation {
+    pub request_id: u64,
+    pub registration_id: u64,
+    /// Procedure the caller asked for
+    pub procedure: String,
+    /// Invocation policy of the registration this arrived through
+    pub invoke: InvokePolicy,
+    /// Typed view of `details`
+    pub info: InvocationDetails,
+    /// Set when the call was made in Payload PassThru Mode
+    pub ppt: Option<PptOptions>,
+    pub details: WampHash,
+    pub args: WampArray,
+    pub kwargs: WampHash,
+
+    // Further chunks of a progressive invocation
+    pub(crate) chunks: Option<Receiver<Invocation>>,
+}
+
+impl Invocation {
+    /// True when more chunks of this call are on the way
+    pub fn is_progress(&self) -> bool {
+        is_progress(&self.details)
+    }
+
+    /// This invocation followed by any further chunks the caller sends
+    /// as part of a progressive call. The last item is the final chunk.
+    pub fn into_stream(mut self) -> impl Stream<Item = Invocation> {
+        let chunks = self.chunks.take();
+        stream::unfold((Some(self), chunks), |(first, chunks)| async move {
+            if let Some(first) = first {
+                return Some((first, (None, chunks)));
+            }
+            match chunks {
+                Some(ref receiver) => {
+                    match receiver.recv().await {
+                        Ok(chunk) => Some((chunk, (None, chunks))),
+                        Err(_) => None,
+                    }
+                },
+                None => None,
+            }
+        })
+    }
+
+    /// Whether the caller asked for progressive results, in which case
+    /// WampClient::yield_progress may be used before returning
+    pub fn receive_progress(&self) -> bool {
+        matches!(self.details.get("receive_progress").map(|v| v.as_ref()), Some(WampData::Bool(true)))
+    }
+
+    pub(crate) fn new(request_id:u64, registration_id:u64, details:WampHash, args:WampArray, kwargs:WampHash) -> Invocation {
+        Invocation {
+            request_id,
+            registration_id,
+            procedure: str_in(&details, "procedure"),
+            invoke: InvokePolicy::Single,
+            info: InvocationDetails::from_details(&details),
+            ppt: PptOptions::from_details(&details),
+            details,
+            args,
+            kwargs,
+            chunks: None,
+        }
+    }
+}
+
+/*
+ * What a registered procedure hands back to its caller
+ */
+#[derive(Debug, Clone, Default)]
+pub struct Yield {
+    pub args: WampArray,
+    pub kwargs: WampHash,
+    /// Payload PassThru Mode for the result. Defaults to whatever the
+    /// call was made with.
+    pub ppt: Option<PptOptions>,
+}
+
+impl Yield {
+    pub fn new(args:WampArray, kwargs:WampHash) -> Yield {
+        Yield { args, kwargs, ppt: None }
+    }
+}
+
+/*
+ * Error returned by a registered procedure, sent back as ERROR
+ */
+#[derive(Debug, Clone)]
+pub struct RpcError {
+    pub error: String,
+    pub args: WampArray,
+    pub kwargs: WampHash,
+}
+
+impl RpcError {
+    pub fn new(error:&str) -> RpcError {
+        RpcError {
+            error: error.to_string(),
+            args: WampArray::new(),
+            kwargs: WampHash::new(),
+        }
+    }
+
+    /// ERROR answering the INVOCATION `request_id`
+    pub(crate) fn into_message(self, request_id:u64) -> WampMessage {
+        WampMessage::Error {
+            request_type: WAMP_INVOCATION,
+            request_id,
+            details: WampHash::new(),
+            error: self.error,
+            args: self.args,
+            kwargs: self.kwargs,
+        }
+    }
+}
+
+/// Lets procedures use `?` on client calls. A failed call passes the
+/// callee's error on, anything else is a runtime error.
+impl From<WampError> for RpcError {
+    fn from(e:WampError) -> Self {
+        match e {
+            WampError::CallFailed(error) => error,
+            e => {
+                let mut error = RpcError::new("wamp.error.runtime_error");
+                error.args.push(e.to_string().into());
+                error
+            },
+        }
+    }
+}
@@ -0,0 +1,595 @@
+use std::future::Future;
+
+// To help us build Builders
+use derive_builder::Builder;
+
+use crate::serialization::WampData;
+use crate::wdata;
+use crate::{WampError, WampHash, WampArray};
+use super::call::CallResult;
+use super::handlers::{opt_str_in, str_in, u64_in, Event};
+use super::options::{InvokePolicy, MatchPolicy, PublishOptions};
+use super::payload::PptOptions;
+use super::WampClient;
+
+/*
+ * Typed access to the router's session, registration and subscription
+ * meta API. Everything here is a thin layer over call and subscribe.
+ */
+
+fn first_arg(result:&CallResult) -> Result<&WampData, WampError> {
+    result.args.first().ok_or(WampError::IncorrectElementCount)
+}
+
+fn ids_in(data:&WampData) -> Result<Vec<u64>, WampError> {
+    match data {
+        WampData::Array(a, _) => a.iter().map(|v| v.as_u64()).collect(),
+        _ => Err(WampError::NotArray),
+    }
+}
+
+/// Hash payload, as found in meta results and meta event arguments
+fn hash_in(data:&WampData) -> Result<WampHash, WampError> {
+    match data {
+        WampData::Hash(h, _) => Ok(*h.clone()),
+        _ => Err(WampError::NotHash),
+    }
+}
+
+fn opt_id(data:&WampData) -> Result<Option<u64>, WampError> {
+    match data {
+        WampData::None => Ok(None),
+        v => Ok(Some(v.as_u64()?)),
+    }
+}
+
+fn strings(values:&[&str]) -> WampData {
+    let values = values.iter().map(|v| WampData::from(*v)).collect();
+    WampData::Array(Box::new(values), 0)
+}
+
+/// Router role and feature a meta procedure or event is provided under.
+/// `role` is whoever handles it, the dealer for calls and the broker for
+/// events, except for the features only one of them announces.
+fn meta_feature(role:&'static str, name:&str) -> Option<(&'static str, &'static str)> {
+    match name {
+        "wamp.session.add_testament" | "wamp.session.flush_testaments" => Some((role, "testament_meta_api")),
+        "wamp.subscription.get_events" => Some(("broker", "event_history")),
+        n if n.starts_with("wamp.session.") => Some((role, "session_meta_api")),
+        n if n.starts_with("wamp.registration.") => Some(("dealer", "registration_meta_api")),
+        n if n.starts_with("wamp.subscription.") => Some(("broker", "subscription_meta_api")),
+        _ => None,
+    }
+}
+
+/// Optional `reason` and `message` of the kill procedures
+fn kill_kwargs(reason:Option<&str>, message:Option<&str>) -> WampData {
+    let mut kwargs = WampHash::new();
+    if let Some(reason) = reason {
+        kwargs.insert("reason".to_string(), Box::new(reason.into()));
+    }
+    if let Some(message) = message {
+        kwargs.insert("message".to_string(), Box::new(message.into()));
+    }
+    WampData::Hash(Box::new(kwargs), 0)
+}
+
+/// Event as stored in the router's event history
+fn history_event(subscription_id:u64, mut details:WampHash) -> Result<Event, WampError> {
+    let args = match details.remove("args").map(|v| *v) {
+        Some(WampData::Array(a, _)) => *a,
+        _ => WampArray::new(),
+    };
+    let kwargs = match details.remove("kwargs").map(|v| *v) {
+        Some(WampData::Hash(h, _)) => *h,
+        _ => WampHash::new(),
+    };
+    Ok(Event {
+        subscription_id,
+        publication_id: u64_in(&details, "publication").ok_or(WampError::InvalidField)?,
+        topic: str_in(&details, "topic"),
+        publisher: u64_in(&details, "publisher"),
+        publisher_authid: opt_str_in(&details, "publisher_authid"),
+        publisher_authrole: opt_str_in(&details, "publisher_authrole"),
+        ppt: PptOptions::from_details(&details),
+        details,
+        args,
+        kwargs,
+    })
+}
+
+/*
+ * A session attached to the router
+ */
+#[derive(Debug, Clone)]
+pub struct SessionInfo {
+    pub session_id: u64,
+    pub authid: Option<String>,
+    pub authrole: Option<String>,
+    pub authmethod: Option<String>,
+    pub authprovider: Option<String>,
+    pub transport: WampHash,
+}
+
+impl SessionInfo {
+    fn from_details(details:&WampHash) -> Result<SessionInfo, WampError> {
+        Ok(SessionInfo {
+            session_id: u64_in(details, "session").ok_or(WampError::InvalidField)?,
+            authid: opt_str_in(details, "authid"),
+            authrole: opt_str_in(details, "authrole"),
+            authmethod: opt_str_in(details, "authmethod"),
+            authprovider: opt_str_in(details, "authprovider"),
+            transport: match details.get("transport") {
+                Some(transport) => hash_in(transport)?,
+                None => WampHash::new(),
+            },
+        })
+    }
+}
+
+/*
+ * When the router publishes a testament
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TestamentScope {
+    /// When the session ends
+    #[default]
+    Destroyed,
+    /// When the session loses its transport
+    Detached,
+}
+
+impl TestamentScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TestamentScope::Destroyed => "destroyed",
+            TestamentScope::Detached => "detached",
+        }
+    }
+}
+
+/*
+ * Event the router publishes on our behalf once our session goes away
+ */
+#[derive(Debug, Clone, Builder)]
+#[builder(setter(into), build_fn(error = "WampError"))]
+pub struct Testament {
+    pub topic: String,
+
+    #[builder(default = "WampData::Array(Box::new(WampArray::new()), 0)")]
+    pub args: WampData,
+
+    #[builder(default = "WampData::Hash(Box::new(WampHash::new()), 0)")]
+    pub kwargs: WampData,
+
+    #[builder(default)]
+    pub scope: TestamentScope,
+
+    /// Options the testament is published with
+    #[builder(default)]
+    pub publish_options: PublishOptions,
+}
+
+/*
+ * Payload of wamp.session.on_leave
+ */
+#[derive(Debug, Clone)]
+pub struct SessionLeft {
+    pub session_id: u64,
+    pub authid: Option<String>,
+    pub authrole: Option<String>,
+}
+
+/*
+ * Registration or subscription ids on the router, by match policy
+ */
+#[derive(Debug, Clone, Default)]
+pub struct MatchLists {
+    pub exact: Vec<u64>,
+    pub prefix: Vec<u64>,
+    pub wildcard: Vec<u64>,
+}
+
+impl MatchLists {
+    fn from_data(data:&WampData) -> Result<MatchLists, WampError> {
+        let list = |key| match data.h(key) {
+            Ok(ids) => ids_in(ids),
+            Err(WampError::InvalidField) => Ok(Vec::new()),
+            Err(e) => Err(e),
+        };
+        Ok(MatchLists {
+            exact: list("exact")?,
+            prefix: list("prefix")?,
+            wildcard: list("wildcard")?,
+        })
+    }
+}
+
+fn match_policy_in(details:&WampHash) -> MatchPolicy {
+    MatchPolicy::parse(&str_in(details, "match")).unwrap_or_default()
+}
+
+/*
+ * A registration as described by wamp.registration.get
+ */
+#[derive(Debug, Clone)]
+pub struct RegistrationInfo {
+    pub registration_id: u64,
+    /// ISO 8601 timestamp of when the registration was created
+    pub created: String,
+    pub procedure: String,
+    pub match_policy: MatchPolicy,
+    pub invoke: InvokePolicy,
+}
+
+impl RegistrationInfo {
+    fn from_details(details:&WampHash) -> Result<RegistrationInfo, WampError> {
+        Ok(RegistrationInfo {
+            registration_id: u64_in(details, "id").ok_or(WampError::InvalidField)?,
+            created: str_in(details, "created"),
+            procedure: str_in(details, "uri"),
+            match_policy: match_policy_in(details),
+            invoke: InvokePolicy::parse(&str_in(details, "invoke")).unwrap_or_default(),
+        })
+    }
+}
+
+/*
+ * A subscription as described by wamp.subscription.get
+ */
+#[derive(Debug, Clone)]
+pub struct SubscriptionInfo {
+    pub subscription_id: u64,
+    /// ISO 8601 timestamp of when the subscription was created
+    pub created: String,
+    pub topic: String,
+    pub match_policy: MatchPolicy,
+}
+
+impl SubscriptionInfo {
+    fn from_details(details:&WampHash) -> Result<SubscriptionInfo, WampError> {
+        Ok(SubscriptionInfo {
+            subscription_id: u64_in(details, "id").ok_or(WampError::InvalidField)?,
+            created: str_in(details, "created"),
+            topic: str_in(details, "uri"),
+            match_policy: match_policy_in(details),
+        })
+    }
+}
+
+/*
+ * Payload of wamp.registration.on_create
+ */
+#[derive(Debug, Clone)]
+pub struct RegistrationCreated {
+    pub session_id: u64,
+    pub registration: RegistrationInfo,
+}
+
+/*
+ * Payload of wamp.subscription.on_create
+ */
+#[derive(Debug, Clone)]
+pub struct SubscriptionCreated {
+    pub session_id: u64,
+    pub subscription: SubscriptionInfo,
+}
+
+/*
+ * Payload of the on_register, on_unregister, on_delete, on_subscribe
+ * and on_unsubscribe meta events: which session touched which
+ * registration or subscription
+ */
+#[derive(Debug, Clone)]
+pub struct MetaChange {
+    pub session_id: u64,
+    pub id: u64,
+}
+
+impl MetaChange {
+    fn from_event(event:&Event) -> Result<MetaChange, WampError> {
+        Ok(MetaChange {
+            session_id: event.args.first().ok_or(WampError::IncorrectElementCount)?.as_u64()?,
+            id: event.args.get(1).ok_or(WampError::IncorrectElementCount)?.as_u64()?,
+        })
+    }
+}
+
+impl WampClient {
+
+    async fn meta_call(&mut self, procedure:&str, args:WampArray, kwargs:WampData) -> Result<CallResult, WampError> {
+        if let Some((role, feature)) = meta_feature("dealer", procedure) {
+            self.require_features(role, &[feature]).await?;
+        }
+        self.call(procedure, WampData::Array(Box::new(args), 0), kwargs).await
+    }
+
+    /// Subscribes to a meta event, handing `cb` the decoded payload.
+    /// Events that don't decode are dropped.
+    async fn meta_subscribe<T, P, F, Fut>(&mut self, topic:&str, parse:P, cb:F) -> Result<u64, WampError>
+    where
+        T: Send + 'static,
+        P: Fn(&Event) -> Result<T, WampError> + Send + Sync + 'static,
+        F: Fn(WampClient, T) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        if let Some((role, feature)) = meta_feature("broker", topic) {
+            self.require_features(role, &[feature]).await?;
+        }
+        let topic_name = topic.to_string();
+        self.subscribe(topic, move |client, event| {
+            let payload = parse(&event);
+            let future = match payload {
+                Ok(payload) => Some(cb(client, payload)),
+                Err(e) => {
+                    println!("Dropping malformed {} event: {:?}", topic_name, e);
+                    None
+                }
+            };
+            async move {
+                if let Some(future) = future {
+                    future.await;
+                }
+            }
+        }).await
+    }
+
+    /// Number of sessions attached, optionally only those with one of
+    /// `authroles`
+    pub async fn session_count(&mut self, authroles:&[&str]) -> Result<u64, WampError> {
+        let args = if authroles.is_empty() { vec![] } else { vec![strings(authroles)] };
+        let result = self.meta_call("wamp.session.count", args, wdata!({})).await?;
+        first_arg(&result)?.as_u64()
+    }
+
+    /// Ids of the sessions attached, optionally only those with one of
+    /// `authroles`
+    pub async fn session_list(&mut self, authroles:&[&str]) -> Result<Vec<u64>, WampError> {
+        let args = if authroles.is_empty() { vec![] } else { vec![strings(authroles)] };
+        let result = self.meta_call("wamp.session.list", args, wdata!({})).await?;
+        ids_in(first_arg(&result)?)
+    }
+
+    pub async fn session_get(&mut self, session_id:u64) -> Result<SessionInfo, WampError> {
+        let result = self.meta_call("wamp.session.get", vec![session_id.into()], wdata!({})).await?;
+        SessionInfo::from_details(&hash_in(first_arg(&result)?)?)
+    }
+
+    pub async fn session_kill(&mut self, session_id:u64, reason:Option<&str>, message:Option<&str>) -> Result<(), WampError> {
+        self.meta_call("wamp.session.kill", vec![session_id.into()], kill_kwargs(reason, message)).await?;
+        Ok(())
+    }
+
+    /// Kills every session authenticated as `authid`, returning their ids
+    pub async fn session_kill_by_authid(&mut self, authid:&str, reason:Option<&str>, message:Option<&str>) -> Result<Vec<u64>, WampError> {
+        let result = self.meta_call("wamp.session.kill_by_authid", vec![authid.into()], kill_kwargs(reason, message)).await?;
+        ids_in(first_arg(&result)?)
+    }
+
+    /// Kills every session with `authrole`, returning how many were killed
+    pub async fn session_kill_by_authrole(&mut self, authrole:&str, reason:Option<&str>, message:Option<&str>) -> Result<u64, WampError> {
+        let result = self.meta_call("wamp.session.kill_by_authrole", vec![authrole.into()], kill_kwargs(reason, message)).await?;
+        first_arg(&result)?.as_u64()
+    }
+
+    pub(crate) async fn install_testament(&mut self, testament:&Testament) -> Result<(), WampError> {
+        let kwargs = wdata!({
+                        "publish_options": (WampData::Hash(Box::new(testament.publish_options.to_details()), 0)),
+                        "scope": (testament.scope.as_str()),
+                    });
+        let args = vec![testament.topic.clone().into(), testament.args.clone(), testament.kwargs.clone()];
+        self.meta_call("wamp.session.add_testament", args, kwargs).await?;
+        Ok(())
+    }
+
+    /// Has the router publish `testament` when our session goes away.
+    /// Testaments are added again after a reconnect.
+    pub async fn add_testament(&mut self, testament:Testament) -> Result<(), WampError> {
+        self.install_testament(&testament).await?;
+        self.tracker.lock().await.testaments.push(testament);
+        Ok(())
+    }
+
+    /// Removes the testaments in `scope`, returning how many the router
+    /// dropped
+    pub async fn flush_testaments(&mut self, scope:TestamentScope) -> Result<u64, WampError> {
+        let result = self.meta_call("wamp.session.flush_testaments", vec![], wdata!({ "scope": (scope.as_str()), })).await?;
+        self.tracker.lock().await.testaments.retain(|testament| testament.scope != scope);
+        first_arg(&result)?.as_u64()
+    }
+
+    pub async fn registration_list(&mut self) -> Result<MatchLists, WampError> {
+        let result = self.meta_call("wamp.registration.list", vec![], wdata!({})).await?;
+        MatchLists::from_data(first_arg(&result)?)
+    }
+
+    /// Registration for exactly `procedure` under `match_policy`
+    pub async fn registration_lookup(&mut self, procedure:&str, match_policy:MatchPolicy) -> Result<Option<u64>, WampError> {
+        let options = wdata!({ "match": (match_policy.as_str()), });
+        let result = self.meta_call("wamp.registration.lookup", vec![procedure.into(), options], wdata!({})).await?;
+        opt_id(first_arg(&result)?)
+    }
+
+    /// Registration a call to `procedure` would be routed to
+    pub async fn registration_match(&mut self, procedure:&str) -> Result<Option<u64>, WampError> {
+        let result = self.meta_call("wamp.registration.match", vec![procedure.into()], wdata!({})).await?;
+        opt_id(first_arg(&result)?)
+    }
+
+    pub async fn registration_get(&mut self, registration_id:u64) -> Result<RegistrationInfo, WampError> {
+        let result = self.meta_call("wamp.registration.get", vec![registration_id.into()], wdata!({})).await?;
+        RegistrationInfo::from_details(&hash_in(first_arg(&result)?)?)
+    }
+
+    /// Session ids of the callees attached to a registration
+    pub async fn registration_list_callees(&mut self, registration_id:u64) -> Result<Vec<u64>, WampError> {
+        let result = self.meta_call("wamp.registration.list_callees", vec![registration_id.into()], wdata!({})).await?;
+        ids_in(first_arg(&result)?)
+    }
+
+    pub async fn registration_count_callees(&mut self, registration_id:u64) -> Result<u64, WampError> {
+        let result = self.meta_call("wamp.registration.count_callees", vec![registration_id.into()], wdata!({})).await?;
+        first_arg(&result)?.as_u64()
+    }
+
+    /// Forcefully removes a callee from a registration
+    pub async fn registration_remove_callee(&mut self, registration_id:u64, callee_id:u64, reason:Option<&str>) -> Result<(), WampError> {
+        self.meta_call("wamp.registration.remove_callee", vec![registration_id.into(), callee_id.into()], kill_kwargs(reason, None)).await?;
+        Ok(())
+    }
+
+    pub async fn subscription_list(&mut self) -> Result<MatchLists, WampError> {
+        let result = self.meta_call("wamp.subscription.list", vec![], wdata!({})).await?;
+        MatchLists::from_data(first_arg(&result)?)
+    }
+
+    /// Subscription for exactly `topic` under `match_policy`
+    pub async fn subscription_lookup(&mut self, topic:&str, match_policy:MatchPolicy) -> Result<Option<u64>, WampError> {
+        let options = wdata!({ "match": (match_policy.as_str()), });
+        let result = self.meta_call("wamp.subscription.lookup", vec![topic.into(), options], wdata!({})).await?;
+        opt_id(first_arg(&result)?)
+    }
+
+    /// Subscriptions an event published to `topic` would be delivered to
+    pub async fn subscription_match(&mut self, topic:&str) -> Result<Vec<u64>, WampError> {
+        let result = self.meta_call("wamp.subscription.match", vec![topic.into()], wdata!({})).await?;
+        match first_arg(&result)? {
+            WampData::None => Ok(Vec::new()),
+            ids => ids_in(ids),
+        }
+    }
+
+    pub async fn subscription_get(&mut self, subscription_id:u64) -> Result<SubscriptionInfo, WampError> {
+        let result = self.meta_call("wamp.subscription.get", vec![subscription_id.into()], wdata!({})).await?;
+        SubscriptionInfo::from_details(&hash_in(first_arg(&result)?)?)
+    }
+
+    /// Up to `limit` events the router kept for a subscription, oldest
+    /// first. Needs the broker's `event_history` feature.
+    pub async fn subscription_history(&mut self, subscription_id:u64, limit:u64) -> Result<Vec<Event>, WampError> {
+        let result = self.meta_call("wamp.subscription.get_events", vec![subscription_id.into(), limit.into()], wdata!({})).await?;
+        match first_arg(&result)? {
+            WampData::Array(events, _) => events.iter().map(|event| history_event(subscription_id, hash_in(event)?)).collect(),
+            _ => Err(WampError::NotArray),
+        }
+    }
+
+    /// Session ids of the subscribers attached to a subscription
+    pub async fn subscription_list_subscribers(&mut self, subscription_id:u64) -> Result<Vec<u64>, WampError> {
+        let result = self.meta_call("wamp.subscription.list_subscribers", vec![subscription_id.into()], wdata!({})).await?;
+        ids_in(first_arg(&result)?)
+    }
+
+    pub async fn subscription_count_subscribers(&mut self, subscription_id:u64) -> Result<u64, WampError> {
+        let result = self.meta_call("wamp.subscription.count_subscribers", vec![subscription_id.into()], wdata!({})).await?;
+        first_arg(&result)?.as_u64()
+    }
+
+    /// Forcefully removes a subscriber from a subscription
+    pub async fn subscription_remove_subscriber(&mut self, subscription_id:u64, subscriber_id:u64, reason:Option<&str>) -> Result<(), WampError> {
+        self.meta_call("wamp.subscription.remove_subscriber", vec![subscription_id.into(), subscriber_id.into()], kill_kwargs(reason, None)).await?;
+        Ok(())
+    }
+
+    pub async fn on_session_join<F, Fut>(&mut self, cb:F) -> Result<u64, WampError>
+    where
+        F: Fn(WampClient, SessionInfo) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.meta_subscribe("wamp.session.on_join", |event| {
+            SessionInfo::from_details(&hash_in(event.args.first().ok_or(WampError::IncorrectElementCount)?)?)
+        }, cb).await
+    }
+
+    pub async fn on_session_leave<F, Fut>(&mut self, cb:F) -> Result<u64, WampError>
+    where
+        F: Fn(WampClient, SessionLeft) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.meta_subscribe("wamp.session.on_leave", |event| {
+            let str_at = |i| match event.args.get(i) {
+                Some(WampData::Str(s)) => Some(s.clone()),
+                _ => None,
+            };
+            Ok(SessionLeft {
+                session_id: event.args.first().ok_or(WampError::IncorrectElementCount)?.as_u64()?,
+                authid: str_at(1),
+                authrole: str_at(2),
+            })
+        }, cb).await
+    }
+
+    pub async fn on_registration_create<F, Fut>(&mut self, cb:F) -> Result<u64, WampError>
+    where
+        F: Fn(WampClient, RegistrationCreated) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.meta_subscribe("wamp.registration.on_create", |event| {
+            Ok(RegistrationCreated {
+                session_id: event.args.first().ok_or(WampError::IncorrectElementCount)?.as_u64()?,
+                registration: RegistrationInfo::from_details(&hash_in(event.args.get(1).ok_or(WampError::IncorrectElementCount)?)?)?,
+            })
+        }, cb).await
+    }
+
+    pub async fn on_registration_register<F, Fut>(&mut self, cb:F) -> Result<u64, WampError>
+    where
+        F: Fn(WampClient, MetaChange) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.meta_subscribe("wamp.registration.on_register", MetaChange::from_event, cb).await
+    }
+
+    pub async fn on_registration_unregister<F, Fut>(&mut self, cb:F) -> Result<u64, WampError>
+    where
+        F: Fn(WampClient, MetaChange) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.meta_subscribe("wamp.registration.on_unregister", MetaChange::from_event, cb).await
+    }
+
+    pub async fn on_registration_delete<F, Fut>(&mut self, cb:F) -> Result<u64, WampError>
+    where
+        F: Fn(WampClient, MetaChange) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.meta_subscribe("wamp.registration.on_delete", MetaChange::from_event, cb).await
+    }
+
+    pub async fn on_subscription_create<F, Fut>(&mut self, cb:F) -> Result<u64, WampError>
+    where
+        F: Fn(WampClient, SubscriptionCreated) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.meta_subscribe("wamp.subscription.on_create", |event| {
+            Ok(SubscriptionCreated {
+                session_id: event.args.first().ok_or(WampError::IncorrectElementCount)?.as_u64()?,
+                subscription: SubscriptionInfo::from_details(&hash_in(event.args.get(1).ok_or(WampError::IncorrectElementCount)?)?)?,
+            })
+        }, cb).await
+    }
+
+    pub async fn on_subscription_subscribe<F, Fut>(&mut self, cb:F) -> Result<u64, WampError>
+    where
+        F: Fn(WampClient, MetaChange) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.meta_subscribe("wamp.subscription.on_subscribe", MetaChange::from_event, cb).await
+    }
+
+    pub async fn on_subscription_unsubscribe<F, Fut>(&mut self, cb:F) -> Result<u64, WampError>
+    where
+        F: Fn(WampClient, MetaChange) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.meta_subscribe("wamp.subscription.on_unsubscribe", MetaChange::from_event, cb).await
+    }
+
+    pub async fn on_subscription_delete<F, Fut>(&mut self, cb:F) -> Result<u64, WampError>
+    where
+        F: Fn(WampClient, MetaChange) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.meta_subscribe("wamp.subscription.on_delete", MetaChange::from_event, cb).await
+    }
+}
@@ -0,0 +1,238 @@
+// To help us build Builders
+use derive_builder::Builder;
+
+use crate::serialization::WampData;
+use crate::{WampError, WampHash};
+use super::payload::PptOptions;
+
+/*
+ * How a subscription's topic or a registration's procedure is matched
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MatchPolicy {
+    #[default]
+    Exact,
+    /// `com.myapp.sensor.` matches everything below it
+    Prefix,
+    /// Empty components match anything, e.g. `com.myapp..temperature`
+    Wildcard,
+}
+
+impl MatchPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MatchPolicy::Exact => "exact",
+            MatchPolicy::Prefix => "prefix",
+            MatchPolicy::Wildcard => "wildcard",
+        }
+    }
+
+    pub(crate) fn parse(s:&str) -> Option<MatchPolicy> {
+        match s {
+            "exact" => Some(MatchPolicy::Exact),
+            "prefix" => Some(MatchPolicy::Prefix),
+            "wildcard" => Some(MatchPolicy::Wildcard),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Builder)]
+#[builder(setter(into), build_fn(error = "WampError"))]
+pub struct SubscribeOptions {
+    #[builder(default)]
+    pub match_policy: MatchPolicy,
+
+    /// After resubscribing on reconnect, replay up to this many events
+    /// from the router's event history that we hadn't seen yet
+    #[builder(setter(strip_option), default)]
+    pub history: Option<u64>,
+}
+
+impl SubscribeOptions {
+    pub fn to_details(&self) -> WampHash {
+        let mut options = WampHash::new();
+        if self.match_policy != MatchPolicy::Exact {
+            options.insert("match".to_string(), Box::new(self.match_policy.as_str().into()));
+        }
+        options
+    }
+
+    /// Broker features these options depend on
+    pub(crate) fn required_features(&self) -> Vec<&'static str> {
+        let mut features = Vec::new();
+        if self.match_policy != MatchPolicy::Exact {
+            features.push("pattern_based_subscription");
+        }
+        if self.history.is_some() {
+            features.push("event_history");
+        }
+        features
+    }
+}
+
+/*
+ * Which callee gets the call when several share a registration
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InvokePolicy {
+    /// Only one callee may register the procedure
+    #[default]
+    Single,
+    RoundRobin,
+    Random,
+    First,
+    Last,
+}
+
+impl InvokePolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            InvokePolicy::Single => "single",
+            InvokePolicy::RoundRobin => "roundrobin",
+            InvokePolicy::Random => "random",
+            InvokePolicy::First => "first",
+            InvokePolicy::Last => "last",
+        }
+    }
+
+    pub(crate) fn parse(s:&str) -> Option<InvokePolicy> {
+        match s {
+            "single" => Some(InvokePolicy::Single),
+            "roundrobin" => Some(InvokePolicy::RoundRobin),
+            "random" => Some(InvokePolicy::Random),
+            "first" => Some(InvokePolicy::First),
+            "last" => Some(InvokePolicy::Last),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Builder)]
+#[builder(setter(into), build_fn(error = "WampError"))]
+pub struct RegisterOptions {
+    #[builder(default)]
+    pub match_policy: MatchPolicy,
+
+    /// Anything other than Single needs the router's
+    /// `shared_registration` feature
+    #[builder(default)]
+    pub invoke: InvokePolicy,
+}
+
+impl RegisterOptions {
+    pub fn to_details(&self) -> WampHash {
+        let mut options = WampHash::new();
+        if self.match_policy != MatchPolicy::Exact {
+            options.insert("match".to_string(), Box::new(self.match_policy.as_str().into()));
+        }
+        if self.invoke != InvokePolicy::Single {
+            options.insert("invoke".to_string(), Box::new(self.invoke.as_str().into()));
+        }
+        options
+    }
+
+    /// Dealer features these options depend on
+    pub(crate) fn required_features(&self) -> Vec<&'static str> {
+        let mut features = Vec::new();
+        if self.match_policy != MatchPolicy::Exact {
+            features.push("pattern_based_registration");
+        }
+        if self.invoke != InvokePolicy::Single {
+            features.push("shared_registration");
+        }
+        features
+    }
+}
+
+#[derive(Debug, Clone, Default, Builder)]
+#[builder(setter(into), build_fn(error = "WampError"))]
+pub struct PublishOptions {
+    /// Whether we receive our own publication if subscribed. The router
+    /// defaults to excluding us.
+    #[builder(setter(strip_option), default)]
+    pub exclude_me: Option<bool>,
+
+    /// Session ids that must not receive the event
+    #[builder(default)]
+    pub exclude: Vec<u64>,
+
+    #[builder(default)]
+    pub exclude_authid: Vec<String>,
+
+    #[builder(default)]
+    pub exclude_authrole: Vec<String>,
+
+    /// When set only these session ids may receive the event
+    #[builder(default)]
+    pub eligible: Vec<u64>,
+
+    #[builder(default)]
+    pub eligible_authid: Vec<String>,
+
+    #[builder(default)]
+    pub eligible_authrole: Vec<String>,
+
+    /// Ask the router to tell subscribers who published
+    #[builder(default)]
+    pub disclose_me: bool,
+
+    /// Wait for PUBLISHED so publish returns the publication id
+    #[builder(default)]
+    pub acknowledge: bool,
+
+    /// Send args and kwargs in Payload PassThru Mode
+    #[builder(setter(strip_option), default)]
+    pub ppt: Option<PptOptions>,
+}
+
+fn insert_list<T:Clone + Into<WampData>>(options:&mut WampHash, key:&str, values:&[T]) {
+    if !values.is_empty() {
+        let values = values.iter().cloned().map(|v| v.into()).collect();
+        options.insert(key.to_string(), Box::new(WampData::Array(Box::new(values), 0)));
+    }
+}
+
+impl PublishOptions {
+    pub fn to_details(&self) -> WampHash {
+        let mut options = WampHash::new();
+        if let Some(exclude_me) = self.exclude_me {
+            options.insert("exclude_me".to_string(), Box::new(WampData::Bool(exclude_me)));
+        }
+        insert_list(&mut options, "exclude", &self.exclude);
+        insert_list(&mut options, "exclude_authid", &self.exclude_authid);
+        insert_list(&mut options, "exclude_authrole", &self.exclude_authrole);
+        insert_list(&mut options, "eligible", &self.eligible);
+        insert_list(&mut options, "eligible_authid", &self.eligible_authid);
+        insert_list(&mut options, "eligible_authrole", &self.eligible_authrole);
+        if self.disclose_me {
+            options.insert("disclose_me".to_string(), Box::new(WampData::Bool(true)));
+        }
+        if self.acknowledge {
+            options.insert("acknowledge".to_string(), Box::new(WampData::Bool(true)));
+        }
+        if let Some(ref ppt) = self.ppt {
+            ppt.apply(&mut options);
+        }
+        options
+    }
+
+    /// Broker features these options depend on
+    pub(crate) fn required_features(&self) -> Vec<&'static str> {
+        let mut features = Vec::new();
+        if self.exclude_me == Some(false) {
+            features.push("publisher_exclusion");
+        }
+        let lists = [&self.exclude_authid, &self.exclude_authrole, &self.eligible_authid, &self.eligible_authrole];
+        if !self.exclude.is_empty() || !self.eligible.is_empty() || lists.iter().any(|list| !list.is_empty()) {
+            features.push("subscriber_blackwhite_listing");
+        }
+        if self.disclose_me {
+            features.push("publisher_identification");
+        }
+        if self.ppt.is_some() {
+            features.push("payload_passthru_mode");
+        }
+        features
+    }
+}
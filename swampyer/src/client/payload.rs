@@ -0,0 +1,216 @@
+// To help us build Builders
+use derive_builder::Builder;
+
+use crate::serialization::{Serializer, WampData};
+use crate::{WampError, WampHash, WampArray};
+use super::handlers::opt_str_in;
+use super::WampClient;
+
+/*
+ * Payload PassThru Mode options. When present the router forwards the
+ * payload untouched, so it may be encrypted or in a foreign format.
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Builder)]
+#[builder(setter(into), build_fn(error = "WampError"))]
+pub struct PptOptions {
+    /// `wamp` for end-to-end encryption, `mqtt`, or an `x_` custom scheme
+    pub scheme: String,
+
+    /// How args and kwargs are serialized inside the payload
+    #[builder(setter(strip_option), default)]
+    pub serializer: Option<String>,
+
+    #[builder(setter(strip_option), default)]
+    pub cipher: Option<String>,
+
+    /// Which key the payload is encrypted with
+    #[builder(setter(strip_option), default)]
+    pub keyid: Option<String>,
+}
+
+impl PptOptions {
+    /// End-to-end encryption with XSalsa20-Poly1305 over CBOR
+    pub fn e2ee(keyid:&str) -> PptOptions {
+        PptOptions {
+            scheme: "wamp".to_string(),
+            serializer: Some("cbor".to_string()),
+            cipher: Some("xsalsa20poly1305".to_string()),
+            keyid: Some(keyid.to_string()),
+        }
+    }
+
+    /// Adds the `ppt_*` entries to a message's options or details
+    pub(crate) fn apply(&self, options:&mut WampHash) {
+        options.insert("ppt_scheme".to_string(), Box::new(self.scheme.as_str().into()));
+        let optional = [
+            ("ppt_serializer", &self.serializer),
+            ("ppt_cipher", &self.cipher),
+            ("ppt_keyid", &self.keyid),
+        ];
+        for (key, value) in optional {
+            if let Some(value) = value {
+                options.insert(key.to_string(), Box::new(value.as_str().into()));
+            }
+        }
+    }
+
+    pub(crate) fn from_details(details:&WampHash) -> Option<PptOptions> {
+        Some(PptOptions {
+            scheme: opt_str_in(details, "ppt_scheme")?,
+            serializer: opt_str_in(details, "ppt_serializer"),
+            cipher: opt_str_in(details, "ppt_cipher"),
+            keyid: opt_str_in(details, "ppt_keyid"),
+        })
+    }
+
+    /// Serializer named by `ppt_serializer`, CBOR when unset
+    pub fn payload_serializer(&self) -> Result<Serializer, WampError> {
+        match self.serializer.as_deref() {
+            None => Ok(Serializer::Cbor),
+            Some(name) => Serializer::from_name(name)
+                            .ok_or_else(|| WampError::PayloadError(format!("unsupported ppt_serializer {}", name))),
+        }
+    }
+}
+
+/*
+ * Turns args and kwargs into the single positional argument sent in
+ * Payload PassThru Mode and back again. Set one on the client with
+ * WampClientBuilder::payload_codec and every message carrying
+ * PptOptions in a scheme it handles goes through it.
+ */
+pub trait PayloadCodec: Send + Sync {
+    /// Whether payloads in `scheme` go through this codec. Other schemes
+    /// are passed through as they are. Defaults to `wamp`, the end-to-end
+    /// encryption scheme.
+    fn handles(&self, scheme:&str) -> bool {
+        scheme == "wamp"
+    }
+
+    fn encode(&self, ppt:&PptOptions, args:&WampData, kwargs:&WampData) -> Result<WampData, WampError>;
+
+    fn decode(&self, ppt:&PptOptions, payload:&WampData) -> Result<(WampData, WampData), WampError>;
+}
+
+/*
+ * End-to-end encryption using NaCl secretbox (XSalsa20-Poly1305) with
+ * pre-shared keys looked up by `ppt_keyid`. The payload is the nonce
+ * followed by the ciphertext.
+ */
+#[cfg(feature = "cryptobox")]
+#[derive(Clone, Default)]
+pub struct CryptoboxCodec {
+    keys: std::collections::HashMap<String, [u8; 32]>,
+}
+
+#[cfg(feature = "cryptobox")]
+impl CryptoboxCodec {
+    pub fn new() -> CryptoboxCodec {
+        CryptoboxCodec::default()
+    }
+
+    pub fn key(mut self, keyid:&str, key:[u8; 32]) -> Self {
+        self.keys.insert(keyid.to_string(), key);
+        self
+    }
+
+    fn cipher(&self, ppt:&PptOptions) -> Result<crypto_secretbox::XSalsa20Poly1305, WampError> {
+        use crypto_secretbox::KeyInit;
+
+        if let Some(ref cipher) = ppt.cipher {
+            if cipher != "xsalsa20poly1305" {
+                return Err(WampError::PayloadError(format!("unsupported ppt_cipher {}", cipher)));
+            }
+        }
+        let keyid = ppt.keyid.as_deref().unwrap_or_default();
+        match self.keys.get(keyid) {
+            Some(key) => Ok(crypto_secretbox::XSalsa20Poly1305::new(key.into())),
+            None => Err(WampError::PayloadError(format!("no key for ppt_keyid {:?}", keyid))),
+        }
+    }
+}
+
+#[cfg(feature = "cryptobox")]
+impl PayloadCodec for CryptoboxCodec {
+    fn encode(&self, ppt:&PptOptions, args:&WampData, kwargs:&WampData) -> Result<WampData, WampError> {
+        use crypto_secretbox::aead::{Aead, AeadCore, OsRng};
+        use crypto_secretbox::XSalsa20Poly1305;
+
+        let mut payload = WampHash::new();
+        payload.insert("args".to_string(), Box::new(args.clone()));
+        payload.insert("kwargs".to_string(), Box::new(kwargs.clone()));
+        let plaintext = ppt.payload_serializer()?.encode(&WampData::Hash(Box::new(payload), 0));
+
+        let nonce = XSalsa20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self.cipher(ppt)?
+                             .encrypt(&nonce, plaintext.as_ref())
+                             .map_err(|_| WampError::PayloadError("encryption failed".to_string()))?;
+
+        let mut sealed = nonce.to_vec();
+        sealed.extend(ciphertext);
+        Ok(WampData::Bytes(sealed))
+    }
+
+    fn decode(&self, ppt:&PptOptions, payload:&WampData) -> Result<(WampData, WampData), WampError> {
+        use crypto_secretbox::aead::Aead;
+        use crypto_secretbox::Nonce;
+
+        let sealed = payload.as_bytes()
+                            .map_err(|_| WampError::PayloadError("payload is not binary".to_string()))?;
+        if sealed.len() < 24 {
+            return Err(WampError::PayloadError("payload is too short".to_string()));
+        }
+        let (nonce, ciphertext) = sealed.split_at(24);
+        let plaintext = self.cipher(ppt)?
+                            .decrypt(Nonce::from_slice(nonce), ciphertext)
+                            .map_err(|_| WampError::PayloadError("decryption failed".to_string()))?;
+
+        let payload = ppt.payload_serializer()?.decode(plaintext)?;
+        let args = payload.h("args").cloned().unwrap_or_else(|_| WampData::Array(Box::default(), 0));
+        let kwargs = payload.h("kwargs").cloned().unwrap_or_else(|_| WampData::Hash(Box::default(), 0));
+        Ok((args, kwargs))
+    }
+}
+
+impl WampClient {
+
+    /// Codec that handles the scheme of `ppt`, if any
+    fn ppt_codec(&self, ppt:&PptOptions) -> Option<&dyn PayloadCodec> {
+        self.config.payload_codec.as_deref().filter(|codec| codec.handles(&ppt.scheme))
+    }
+
+    /// Args and kwargs to put on the wire for an outgoing message. With
+    /// PptOptions in a scheme the codec handles they become the codec's
+    /// single payload; otherwise they are sent as given, unless the
+    /// options ask for encryption which would go out in plaintext.
+    pub(crate) fn ppt_encode(&self, ppt:Option<&PptOptions>, args:WampArray, kwargs:WampHash) -> Result<(WampArray, WampHash), WampError> {
+        let codec = ppt.and_then(|ppt| self.ppt_codec(ppt));
+        match (ppt, codec) {
+            (Some(ppt), Some(codec)) => {
+                let payload = codec.encode(ppt, &WampData::Array(Box::new(args), 0), &WampData::Hash(Box::new(kwargs), 0))?;
+                Ok((vec![payload], WampHash::new()))
+            },
+            (Some(ppt), None) if ppt.cipher.is_some() || ppt.scheme == "wamp" => {
+                Err(WampError::PayloadError(format!("ppt_scheme {} needs a payload codec", ppt.scheme)))
+            },
+            _ => Ok((args, kwargs)),
+        }
+    }
+
+    /// Undoes ppt_encode on an incoming message given its details
+    pub(crate) fn ppt_decode(&self, details:&WampHash, args:WampArray, kwargs:WampHash) -> Result<(WampArray, WampHash), WampError> {
+        let ppt = match PptOptions::from_details(details) {
+            Some(ppt) => ppt,
+            None => return Ok((args, kwargs)),
+        };
+        let codec = match self.ppt_codec(&ppt) {
+            Some(codec) => codec,
+            None => return Ok((args, kwargs)),
+        };
+        let payload = args.first().ok_or(WampError::IncorrectElementCount)?;
+        match codec.decode(&ppt, payload)? {
+            (WampData::Array(args, _), WampData::Hash(kwargs, _)) => Ok((*args, *kwargs)),
+            _ => Err(WampError::PayloadError("payload did not hold args and kwargs".to_string())),
+        }
+    }
+}
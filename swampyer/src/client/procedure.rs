@@ -0,0 +1,80 @@
+use std::sync::Arc;
+
+use crate::convert::{FromWampData, ToWampData};
+use crate::serialization::WampData;
+use crate::{WampError, WampHash};
+use super::call::CallResult;
+use super::handlers::{BoxFuture, Invocation, InvocationHandler, RpcError, Yield};
+use super::options::RegisterOptions;
+use super::WampClient;
+
+/*
+ * A callee defined with `#[swampyer::procedure("uri")]`. Every one in
+ * the program is collected so WampClient::register_procedures can
+ * register them all at once.
+ */
+pub struct Procedure {
+    pub uri: &'static str,
+    handler: fn(WampClient, Invocation) -> BoxFuture<Result<Yield, RpcError>>,
+}
+
+inventory::collect!(Procedure);
+
+impl Procedure {
+    pub const fn new(uri:&'static str, handler:fn(WampClient, Invocation) -> BoxFuture<Result<Yield, RpcError>>) -> Procedure {
+        Procedure { uri, handler }
+    }
+
+    /// Every procedure defined with the attribute
+    pub fn all() -> impl Iterator<Item = &'static Procedure> {
+        inventory::iter::<Procedure>.into_iter()
+    }
+
+    /// Reads parameter `name` from positional arg `i`, or from the
+    /// kwarg of the same name when the caller didn't send that many
+    /// args. A parameter that is missing altogether reads as None so
+    /// Option parameters may be left out.
+    pub fn argument<T: FromWampData>(invocation:&Invocation, i:usize, name:&str) -> Result<T, RpcError> {
+        let value = invocation.args.get(i)
+                        .or_else(|| invocation.kwargs.get(name).map(|v| v.as_ref()));
+        let result = match value {
+            Some(value) => T::from_wamp_data(value),
+            None => T::from_wamp_data(&WampData::None)
+                      .map_err(|_| WampError::ConversionError("missing argument".to_string())),
+        };
+        result.map_err(|e| {
+            let mut error = RpcError::new("wamp.error.invalid_argument");
+            error.args.push(format!("{}: {}", name, e).into());
+            error
+        })
+    }
+
+    /// A procedure's return value as YIELD. Unit yields nothing, any
+    /// other value is the single positional result.
+    pub fn into_yield<T: ToWampData + ?Sized>(value:&T) -> Yield {
+        match value.to_wamp_data() {
+            WampData::None => Yield::default(),
+            data => Yield::new(vec![data], WampHash::new()),
+        }
+    }
+
+    /// Reads a procedure's return value back from the caller's side,
+    /// undoing into_yield
+    pub fn result<T: FromWampData>(result:&CallResult) -> Result<T, WampError> {
+        T::from_wamp_data(result.args.first().unwrap_or(&WampData::None))
+    }
+}
+
+impl WampClient {
+
+    /// Registers every procedure defined with `#[swampyer::procedure]`,
+    /// returning the registration ids in the same order as Procedure::all
+    pub async fn register_procedures(&mut self) -> Result<Vec<u64>, WampError> {
+        let mut registrations = Vec::new();
+        for procedure in Procedure::all() {
+            let handler:InvocationHandler = Arc::new(procedure.handler);
+            registrations.push(self.register_handler(procedure.uri, RegisterOptions::default(), handler).await?);
+        }
+        Ok(registrations)
+    }
+}
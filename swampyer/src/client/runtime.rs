@@ -0,0 +1,158 @@
+use std::future::Future;
+use std::time::Duration;
+
+use futures::io::{AsyncRead, AsyncWrite};
+use futures::future::{self, Either};
+
+use crate::WampError;
+use super::handlers::BoxFuture;
+
+/*
+ * Anything we can speak rawsocket over
+ */
+pub trait Stream: AsyncRead + AsyncWrite + Send + Sync + Unpin {}
+impl<T: AsyncRead + AsyncWrite + Send + Sync + Unpin> Stream for T {}
+
+pub type BoxStream = Box<dyn Stream>;
+
+/*
+ * The few things the client needs from an async runtime. The client
+ * itself never blocks; it only spawns handlers, waits on timers and
+ * opens sockets through this.
+ */
+pub trait Runtime: Send + Sync {
+    fn spawn(&self, future:BoxFuture<()>);
+
+    fn sleep(&self, duration:Duration) -> BoxFuture<()>;
+
+    /// Opens a TCP connection to `url` (`host:port`)
+    fn connect(&self, url:&str) -> BoxFuture<Result<BoxStream, WampError>>;
+}
+
+/// Races `future` against `timeout` on `runtime`'s timer
+pub async fn with_timeout<T, F>(runtime:&dyn Runtime, timeout:Option<Duration>, future:F) -> Result<T, WampError>
+where
+    F: Future<Output = Result<T, WampError>>,
+{
+    let timeout = match timeout {
+        Some(timeout) => timeout,
+        None => return future.await,
+    };
+    futures::pin_mut!(future);
+    match future::select(future, runtime.sleep(timeout)).await {
+        Either::Left((result, _)) => result,
+        Either::Right(_) => Err(WampError::Timeout),
+    }
+}
+
+/*
+ * smol integration. Spawns onto the global smol executor unless an
+ * executor of the caller's choosing is supplied.
+ */
+#[cfg(feature = "smol")]
+#[derive(Clone, Default)]
+pub struct SmolRuntime {
+    executor: Option<std::sync::Arc<smol::Executor<'static>>>,
+}
+
+#[cfg(feature = "smol")]
+impl SmolRuntime {
+    pub fn new() -> SmolRuntime {
+        SmolRuntime { executor: None }
+    }
+
+    pub fn with_executor(executor:std::sync::Arc<smol::Executor<'static>>) -> SmolRuntime {
+        SmolRuntime { executor: Some(executor) }
+    }
+}
+
+#[cfg(feature = "smol")]
+impl Runtime for SmolRuntime {
+    fn spawn(&self, future:BoxFuture<()>) {
+        match self.executor {
+            Some(ref executor) => executor.spawn(future).detach(),
+            None => smol::spawn(future).detach(),
+        }
+    }
+
+    fn sleep(&self, duration:Duration) -> BoxFuture<()> {
+        Box::pin(async move {
+            smol::Timer::after(duration).await;
+        })
+    }
+
+    fn connect(&self, url:&str) -> BoxFuture<Result<BoxStream, WampError>> {
+        let url = url.to_string();
+        Box::pin(async move {
+            match smol::net::TcpStream::connect(url).await {
+                Ok(stream) => Ok(Box::new(stream) as BoxStream),
+                Err(e) => Err(WampError::ConnectionFailure),
+            }
+        })
+    }
+}
+
+/*
+ * tokio integration. Spawns onto the runtime the handle belongs to.
+ */
+#[cfg(feature = "tokio")]
+#[derive(Clone)]
+pub struct TokioRuntime {
+    handle: tokio::runtime::Handle,
+}
+
+#[cfg(feature = "tokio")]
+impl TokioRuntime {
+    /// Uses the runtime we're currently running inside of
+    pub fn new() -> Result<TokioRuntime, WampError> {
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => Ok(TokioRuntime { handle }),
+            Err(_) => Err(WampError::InvalidConfiguration("no tokio runtime running".to_string())),
+        }
+    }
+
+    pub fn with_handle(handle:tokio::runtime::Handle) -> TokioRuntime {
+        TokioRuntime { handle }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Runtime for TokioRuntime {
+    fn spawn(&self, future:BoxFuture<()>) {
+        self.handle.spawn(future);
+    }
+
+    fn sleep(&self, duration:Duration) -> BoxFuture<()> {
+        Box::pin(tokio::time::sleep(duration))
+    }
+
+    fn connect(&self, url:&str) -> BoxFuture<Result<BoxStream, WampError>> {
+        use tokio_util::compat::TokioAsyncReadCompatExt;
+
+        let url = url.to_string();
+        Box::pin(async move {
+            match tokio::net::TcpStream::connect(url).await {
+                Ok(stream) => Ok(Box::new(stream.compat()) as BoxStream),
+                Err(e) => Err(WampError::ConnectionFailure),
+            }
+        })
+    }
+}
+
+/// Runtime used when the builder isn't given one
+pub(crate) fn default_runtime() -> Result<std::sync::Arc<dyn Runtime>, WampError> {
+    #[cfg(feature = "smol")]
+    {
+        Ok(std::sync::Arc::new(SmolRuntime::new()))
+    }
+
+    #[cfg(all(feature = "tokio", not(feature = "smol")))]
+    {
+        Ok(std::sync::Arc::new(TokioRuntime::new()?))
+    }
+
+    #[cfg(not(any(feature = "smol", feature = "tokio")))]
+    {
+        Err(WampError::InvalidConfiguration("runtime must be set".to_string()))
+    }
+}
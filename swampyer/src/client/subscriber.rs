@@ -0,0 +1,67 @@
+use std::sync::Arc;
+
+use crate::convert::FromWampData;
+use crate::serialization::WampData;
+use crate::WampError;
+use super::handlers::{BoxFuture, Event, EventHandler};
+use super::options::SubscribeOptions;
+use super::WampClient;
+
+/*
+ * An event handler defined with `#[swampyer::subscribe("topic")]`.
+ * Every one in the program is collected so
+ * WampClient::subscribe_handlers can subscribe them all at once.
+ */
+pub struct Subscriber {
+    pub topic: &'static str,
+    handler: fn(WampClient, Event) -> BoxFuture<()>,
+}
+
+inventory::collect!(Subscriber);
+
+impl Subscriber {
+    pub const fn new(topic:&'static str, handler:fn(WampClient, Event) -> BoxFuture<()>) -> Subscriber {
+        Subscriber { topic, handler }
+    }
+
+    /// Every handler defined with the attribute
+    pub fn all() -> impl Iterator<Item = &'static Subscriber> {
+        inventory::iter::<Subscriber>.into_iter()
+    }
+
+    /// Reads parameter `name` from the event's positional arg `i`, or
+    /// from the kwarg of the same name, like Procedure::argument
+    pub fn argument<T: FromWampData>(event:&Event, i:usize, name:&str) -> Result<T, WampError> {
+        let value = event.args.get(i)
+                        .or_else(|| event.kwargs.get(name).map(|v| v.as_ref()));
+        let result = match value {
+            Some(value) => T::from_wamp_data(value),
+            None => T::from_wamp_data(&WampData::None)
+                      .map_err(|_| WampError::ConversionError("missing argument".to_string())),
+        };
+        result.map_err(|e| match e {
+            WampError::ConversionError(msg) => WampError::ConversionError(format!("{}: {}", name, msg)),
+            e => e,
+        })
+    }
+
+    /// Reports an event whose args didn't fit through the client's
+    /// onevent_error handler
+    pub async fn malformed(client:&WampClient, event:Event, error:WampError) {
+        client.event_error(event, error).await
+    }
+}
+
+impl WampClient {
+
+    /// Subscribes every handler defined with `#[swampyer::subscribe]`,
+    /// returning the subscription ids in the same order as Subscriber::all
+    pub async fn subscribe_handlers(&mut self) -> Result<Vec<u64>, WampError> {
+        let mut subscriptions = Vec::new();
+        for subscriber in Subscriber::all() {
+            let handler:EventHandler = Arc::new(subscriber.handler);
+            subscriptions.push(self.subscribe_handler(subscriber.topic, SubscribeOptions::default(), handler).await?);
+        }
+        Ok(subscriptions)
+    }
+}
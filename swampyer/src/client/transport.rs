@@ -1,120 +1,163 @@
-use smol::{net, prelude::*};
+use futures::io::{AsyncReadExt, AsyncWriteExt, ReadHalf, WriteHalf};
 use bytes::{BytesMut, BufMut};
 
 use crate::WampError;
+use crate::serialization::Serializer;
+use super::runtime::{Runtime, BoxStream};
 
 /**************************************************************************/
 /**************************************************************************/
 
 const MAGIC:u8 = 0x7f;
-const MAX_BLOCK_SIZE:u8 = 0x30; // 2^12 bytes = 4k
-const SERIALIZER:u8 = 0x03; // CBOR serializer
+const MAX_BLOCK_SIZE:u8 = 0xf0; // 2^24 bytes = 16M
 
 const RAWSOCKET_MESSAGE_TYPE_REGULAR:u8 = 0;
+const RAWSOCKET_MESSAGE_TYPE_PING:u8 = 1;
+const RAWSOCKET_MESSAGE_TYPE_PONG:u8 = 2;
 
-// Connect and say hello to the server. We need to do the
-// handshake here.
-const HANDSHAKE:[u8;4] = [
-                            MAGIC, // Flags to crossbar that we're speaking the same language
-                            MAX_BLOCK_SIZE | SERIALIZER,
-                            0, 0,
-                        ]; 
-
+/*
+ * A single rawsocket frame
+ */
 #[derive(Debug, Clone)]
+pub enum Frame {
+    Message(Vec<u8>),
+    Ping(Vec<u8>),
+    Pong(Vec<u8>),
+}
+
 pub struct Transport {
-    stream: net::TcpStream,
+    stream: BoxStream,
+    serializer: Serializer,
 }
 
-impl Transport {
+impl std::fmt::Debug for Transport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Transport({:?})", self.serializer)
+    }
+}
 
-    pub async fn message_send(&mut self, buf:Vec<u8>) {
-        let mut message_length_buf = BytesMut::with_capacity(10);
-        message_length_buf.put_u8(RAWSOCKET_MESSAGE_TYPE_REGULAR);
-        message_length_buf.put_u8(0);
+/*
+ * Halves of a Transport so reading and writing can happen concurrently
+ */
+pub struct TransportReader {
+    stream: ReadHalf<BoxStream>,
+}
 
-        let message_length = buf.len().try_into().unwrap();
-        message_length_buf.put_u16(message_length);
+pub struct TransportWriter {
+    stream: WriteHalf<BoxStream>,
+}
 
-        // println!("Queueing data: {:?}", message_length_buf.to_vec());
-        self.stream.write(&message_length_buf.to_vec()).await;
-        println!("Queueing {} bytes of data: {:?}", message_length, buf);
-        self.stream.write(&buf).await;
-    }
+impl TransportWriter {
 
-    pub async fn message_get(&mut self) -> Option<Vec<u8>> {
-        let mut buf = vec![0u8; 4096];
+    pub async fn frame_send(&mut self, frame:Frame) -> Result<(), WampError> {
+        let (frame_type, buf) = match frame {
+            Frame::Message(buf) => (RAWSOCKET_MESSAGE_TYPE_REGULAR, buf),
+            Frame::Ping(buf) => (RAWSOCKET_MESSAGE_TYPE_PING, buf),
+            Frame::Pong(buf) => (RAWSOCKET_MESSAGE_TYPE_PONG, buf),
+        };
 
-        let read_bytes_result = self.stream.read(&mut buf).await;
+        let message_length:u32 = match buf.len().try_into() {
+            Ok(l) if l < 1 << 24 => l,
+            _ => return Err(WampError::MessageTooLarge),
+        };
 
-        if let Err(err) = read_bytes_result {
-            println!("ERROR!: {:?}", err);
-            return None;
-        }
+        let mut message_buf = BytesMut::with_capacity(4 + buf.len());
+        message_buf.put_u32(message_length);
+        message_buf[0] = frame_type;
+        message_buf.put_slice(&buf);
 
-        let read_bytes = read_bytes_result.unwrap();
-        println!("GOT chars: {}", read_bytes);
+        match self.stream.write_all(&message_buf).await {
+            Ok(_) => Ok(()),
+            Err(_) => Err(WampError::ConnectionFailure),
+        }
+    }
+}
 
-        // FIXME: need to keep collecting data or dealing with chunked
-        // data or a burst with multiple packets at the same time?
-        if read_bytes < 4 {
-            return None;
+impl TransportReader {
+
+    /// Waits for the next frame. An error means the connection is gone.
+    pub async fn frame_get(&mut self) -> Result<Frame, WampError> {
+        loop {
+            let mut header = [0u8; 4];
+            if let Err(err) = self.stream.read_exact(&mut header).await {
+                println!("ERROR!: {:?}", err);
+                return Err(WampError::ConnectionFailure);
+            }
+
+            let frame_type = header[0] & 0x07;
+            let frame_length = u32::from_be_bytes([0, header[1], header[2], header[3]]);
+
+            let mut buf = vec![0u8; frame_length as usize];
+            if let Err(err) = self.stream.read_exact(&mut buf).await {
+                println!("ERROR!: {:?}", err);
+                return Err(WampError::ConnectionFailure);
+            }
+
+            match frame_type {
+                RAWSOCKET_MESSAGE_TYPE_REGULAR => return Ok(Frame::Message(buf)),
+                RAWSOCKET_MESSAGE_TYPE_PING => return Ok(Frame::Ping(buf)),
+                RAWSOCKET_MESSAGE_TYPE_PONG => return Ok(Frame::Pong(buf)),
+                _ => {},
+            }
         }
+    }
+}
 
-        //FIXME: read_bytes should be 
+impl Transport {
 
-        Some(
-            buf[4..read_bytes].to_vec()
-        )
+    pub fn serializer(&self) -> Serializer {
+        self.serializer
     }
 
-    pub async fn negotiate(&mut self) {
-        let mut buf = vec![0u8; 4096];
+    pub fn split(self) -> (TransportReader, TransportWriter) {
+        let (reader, writer) = self.stream.split();
+        (TransportReader { stream: reader }, TransportWriter { stream: writer })
+    }
 
-        println!("Attempting handshake");
+    pub async fn negotiate(&mut self) -> Result<(), WampError> {
+        let mut buf = [0u8; 4];
 
         // Perform the handshake
 
         // We start things off by doing the raw socket handshake with nexus
         // which determines if this is a nexus server, the protocol to use
         // and so on
-        self.stream.write(&HANDSHAKE).await;
+        let handshake:[u8;4] = [
+                            MAGIC, // Flags to crossbar that we're speaking the same language
+                            MAX_BLOCK_SIZE | self.serializer.rawsocket_id(),
+                            0, 0,
+                        ];
+        if self.stream.write_all(&handshake).await.is_err() {
+            return Err(WampError::ConnectionFailure);
+        }
 
         // Let's get the server's response
-        // FIXME: handle errors properly
-        let read_bytes = self.stream.read(&mut buf).await.unwrap();
-        if read_bytes != 4 {
-            panic!("Did not get 4 bytes!")
+        if self.stream.read_exact(&mut buf).await.is_err() {
+            return Err(WampError::HandshakeFailure);
         }
         if buf[0] != MAGIC {
-            panic!("Did not get MAGIC")
+            println!("Did not get MAGIC");
+            return Err(WampError::HandshakeFailure);
         }
 
         let server_serializer = buf[1] & 0x0f;
-        if server_serializer != SERIALIZER {
-            panic!("Server did not agree to use JSON")
+        if server_serializer != self.serializer.rawsocket_id() {
+            println!("Server did not agree to use {:?} (error code {})", self.serializer, buf[1] >> 4);
+            return Err(WampError::HandshakeFailure);
         }
-        let server_buffer_size = buf[1] >> 4;
-        println!("Server buffer size is: {}", server_buffer_size);
-    }
 
-    pub fn connect( url:&str ) -> Result<Transport, WampError> {
-        let connect_result = smol::block_on(async {
-                            net::TcpStream::connect(url).await
-                        });
-        match connect_result {
-            Ok(stream) => {
-                let mut transport = Transport { stream };
-
-                smol::block_on(async {
-                    transport.negotiate().await;
-                });
-
-                Ok(transport)
-            },
-            Err(e) => Err(WampError::ConnectionFailure),
-        }
+        Ok(())
     }
-}
-
 
+    /// Performs the rawsocket handshake over an already open stream
+    pub async fn from_stream( stream:BoxStream, serializer:Serializer ) -> Result<Transport, WampError> {
+        let mut transport = Transport { stream, serializer };
+        transport.negotiate().await?;
+        Ok(transport)
+    }
 
+    pub async fn connect( runtime:&dyn Runtime, url:&str, serializer:Serializer ) -> Result<Transport, WampError> {
+        let stream = runtime.connect(url).await?;
+        Transport::from_stream(stream, serializer).await
+    }
+}
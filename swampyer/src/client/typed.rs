@@ -0,0 +1,154 @@
+use std::future::Future;
+use std::sync::Arc;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::serialization::WampData;
+use crate::wamp_serde::{from_wamp_data, to_wamp_data};
+use crate::{WampError, WampHash, WampArray};
+use super::call::CallResult;
+use super::handlers::{Event, Invocation, RpcError, Yield};
+use super::WampClient;
+
+/*
+ * Reading args and kwargs as serde types. Positional args read as a
+ * tuple or Vec, kwargs as a struct or map.
+ */
+
+fn args_as<T: DeserializeOwned>(args:&WampArray) -> Result<T, WampError> {
+    from_wamp_data(WampData::Array(Box::new(args.clone()), 0))
+}
+
+fn arg_as<T: DeserializeOwned>(args:&WampArray, i:usize) -> Result<T, WampError> {
+    from_wamp_data(args.get(i).cloned().unwrap_or(WampData::None))
+}
+
+fn kwargs_as<T: DeserializeOwned>(kwargs:&WampHash) -> Result<T, WampError> {
+    from_wamp_data(WampData::Hash(Box::new(kwargs.clone()), 0))
+}
+
+/// ERROR sent back when an invocation's arguments don't fit the
+/// handler's types
+fn invalid_argument(e:WampError) -> RpcError {
+    let mut error = RpcError::new("wamp.error.invalid_argument");
+    error.args.push(e.to_string().into());
+    error
+}
+
+impl CallResult {
+    pub fn args_as<T: DeserializeOwned>(&self) -> Result<T, WampError> {
+        args_as(&self.args)
+    }
+
+    /// Positional argument `i`, read as unit or None when missing
+    pub fn arg_as<T: DeserializeOwned>(&self, i:usize) -> Result<T, WampError> {
+        arg_as(&self.args, i)
+    }
+
+    pub fn kwargs_as<T: DeserializeOwned>(&self) -> Result<T, WampError> {
+        kwargs_as(&self.kwargs)
+    }
+}
+
+impl Event {
+    pub fn args_as<T: DeserializeOwned>(&self) -> Result<T, WampError> {
+        args_as(&self.args)
+    }
+
+    pub fn arg_as<T: DeserializeOwned>(&self, i:usize) -> Result<T, WampError> {
+        arg_as(&self.args, i)
+    }
+
+    pub fn kwargs_as<T: DeserializeOwned>(&self) -> Result<T, WampError> {
+        kwargs_as(&self.kwargs)
+    }
+}
+
+impl Invocation {
+    pub fn args_as<T: DeserializeOwned>(&self) -> Result<T, WampError> {
+        args_as(&self.args)
+    }
+
+    pub fn arg_as<T: DeserializeOwned>(&self, i:usize) -> Result<T, WampError> {
+        arg_as(&self.args, i)
+    }
+
+    pub fn kwargs_as<T: DeserializeOwned>(&self) -> Result<T, WampError> {
+        kwargs_as(&self.kwargs)
+    }
+}
+
+impl Yield {
+    /// Yields `value` as the single positional argument
+    pub fn from_value<T: Serialize>(value:&T) -> Result<Yield, WampError> {
+        Ok(Yield::new(vec![to_wamp_data(value)?], WampHash::new()))
+    }
+}
+
+impl WampClient {
+
+    /// Calls `uri` with serde values. `args` may be a tuple for several
+    /// positional arguments, `kwargs` a struct or map, and either may be
+    /// `()` when there is nothing to send. The result is read from the
+    /// first positional argument, which is what register_typed sends.
+    pub async fn call_typed<A, K, R>(&mut self, uri:&str, args:&A, kwargs:&K) -> Result<R, WampError>
+    where
+        A: Serialize,
+        K: Serialize,
+        R: DeserializeOwned,
+    {
+        let result = self.call(uri, to_wamp_data(args)?, to_wamp_data(kwargs)?).await?;
+        result.arg_as(0)
+    }
+
+    /// Publishes serde values, taking args and kwargs like call_typed
+    pub async fn publish_typed<A, K>(&mut self, topic:&str, args:&A, kwargs:&K) -> Result<Option<u64>, WampError>
+    where
+        A: Serialize,
+        K: Serialize,
+    {
+        self.publish(topic, to_wamp_data(args)?, to_wamp_data(kwargs)?).await
+    }
+
+    /// Subscribes a handler taking the event's positional args as `A`.
+    /// Events whose args don't fit go to the onevent_error handler.
+    pub async fn subscribe_typed<A, F, Fut>(&mut self, topic:&str, cb:F) -> Result<u64, WampError>
+    where
+        A: DeserializeOwned + Send + 'static,
+        F: Fn(WampClient, A) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let cb = Arc::new(cb);
+        self.subscribe(topic, move |client, event:Event| {
+            let cb = cb.clone();
+            async move {
+                match event.args_as::<A>() {
+                    Ok(args) => cb(client, args).await,
+                    Err(e) => client.event_error(event, e).await,
+                }
+            }
+        }).await
+    }
+
+    /// Registers a handler taking the call's positional args as `A` and
+    /// yielding `R` as the single result. Calls whose args don't fit get
+    /// `wamp.error.invalid_argument`.
+    pub async fn register_typed<A, R, F, Fut>(&mut self, procedure:&str, cb:F) -> Result<u64, WampError>
+    where
+        A: DeserializeOwned + Send + 'static,
+        R: Serialize + Send + 'static,
+        F: Fn(WampClient, A) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<R, RpcError>> + Send + 'static,
+    {
+        let cb = Arc::new(cb);
+        self.register(procedure, move |client, invocation:Invocation| {
+            let cb = cb.clone();
+            async move {
+                let args = invocation.args_as::<A>().map_err(invalid_argument)?;
+                let result = cb(client, args).await?;
+                Yield::from_value(&result).map_err(invalid_argument)
+            }
+        }).await
+    }
+}
@@ -0,0 +1,355 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use crate::serialization::{WampData, WampHash, WampArray};
+use crate::WampError;
+
+/*
+ * Conversions between Rust types and WampData. `#[derive(Wamp)]`
+ * implements both for structs and enums, building on the impls here
+ * for primitives and containers.
+ */
+pub trait ToWampData {
+    fn to_wamp_data(&self) -> WampData;
+}
+
+pub trait FromWampData: Sized {
+    fn from_wamp_data(data:&WampData) -> Result<Self, WampError>;
+}
+
+fn mismatch(expected:&str, data:&WampData) -> WampError {
+    WampError::ConversionError(format!("expected {}, got {:?}", expected, data))
+}
+
+/// `data` with any CBOR tag stripped and custom types turned into
+/// plain data so it can be matched on
+pub fn resolve(data:&WampData) -> Result<Cow<'_, WampData>, WampError> {
+    match data {
+        WampData::Tagged(_, v) => resolve(v),
+        WampData::Serializable(_) => Ok(Cow::Owned(*WampData::from_slice(data.to_vec())?)),
+        _ => Ok(Cow::Borrowed(data)),
+    }
+}
+
+impl ToWampData for WampData {
+    fn to_wamp_data(&self) -> WampData {
+        self.clone()
+    }
+}
+
+impl FromWampData for WampData {
+    fn from_wamp_data(data:&WampData) -> Result<Self, WampError> {
+        Ok(data.clone())
+    }
+}
+
+impl ToWampData for () {
+    fn to_wamp_data(&self) -> WampData {
+        WampData::None
+    }
+}
+
+impl FromWampData for () {
+    fn from_wamp_data(data:&WampData) -> Result<Self, WampError> {
+        match data {
+            WampData::None | WampData::Undefined => Ok(()),
+            other => Err(mismatch("null", other)),
+        }
+    }
+}
+
+macro_rules! unsigned {
+    ( $( $t:ty ),* ) => {
+        $(
+            impl ToWampData for $t {
+                fn to_wamp_data(&self) -> WampData {
+                    WampData::UInt(*self as u64)
+                }
+            }
+
+            impl FromWampData for $t {
+                fn from_wamp_data(data:&WampData) -> Result<Self, WampError> {
+                    let data = resolve(data)?;
+                    let value = match *data {
+                        WampData::UInt(u) => <$t>::try_from(u).ok(),
+                        WampData::Int(i) => <$t>::try_from(i).ok(),
+                        WampData::BigInt(i) => <$t>::try_from(i).ok(),
+                        _ => return Err(mismatch(stringify!($t), &data)),
+                    };
+                    value.ok_or_else(|| WampError::ConversionError(format!("{:?} is out of range for {}", data, stringify!($t))))
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! signed {
+    ( $( $t:ty ),* ) => {
+        $(
+            impl ToWampData for $t {
+                fn to_wamp_data(&self) -> WampData {
+                    WampData::Int(*self as i64)
+                }
+            }
+
+            impl FromWampData for $t {
+                fn from_wamp_data(data:&WampData) -> Result<Self, WampError> {
+                    let data = resolve(data)?;
+                    let value = match *data {
+                        WampData::UInt(u) => <$t>::try_from(u).ok(),
+                        WampData::Int(i) => <$t>::try_from(i).ok(),
+                        WampData::BigInt(i) => <$t>::try_from(i).ok(),
+                        _ => return Err(mismatch(stringify!($t), &data)),
+                    };
+                    value.ok_or_else(|| WampError::ConversionError(format!("{:?} is out of range for {}", data, stringify!($t))))
+                }
+            }
+        )*
+    };
+}
+
+unsigned!(u8, u16, u32, u64, usize);
+signed!(i8, i16, i32, i64, isize);
+
+impl ToWampData for f64 {
+    fn to_wamp_data(&self) -> WampData {
+        WampData::Float(*self)
+    }
+}
+
+impl FromWampData for f64 {
+    fn from_wamp_data(data:&WampData) -> Result<Self, WampError> {
+        let data = resolve(data)?;
+        match *data {
+            WampData::Float(f) => Ok(f),
+            WampData::UInt(u) => Ok(u as f64),
+            WampData::Int(i) => Ok(i as f64),
+            WampData::BigInt(i) => Ok(i as f64),
+            _ => Err(mismatch("f64", &data)),
+        }
+    }
+}
+
+impl ToWampData for f32 {
+    fn to_wamp_data(&self) -> WampData {
+        WampData::Float(f64::from(*self))
+    }
+}
+
+impl FromWampData for f32 {
+    fn from_wamp_data(data:&WampData) -> Result<Self, WampError> {
+        f64::from_wamp_data(data).map(|f| f as f32)
+    }
+}
+
+impl ToWampData for bool {
+    fn to_wamp_data(&self) -> WampData {
+        WampData::Bool(*self)
+    }
+}
+
+impl FromWampData for bool {
+    fn from_wamp_data(data:&WampData) -> Result<Self, WampError> {
+        match *resolve(data)? {
+            WampData::Bool(b) => Ok(b),
+            ref other => Err(mismatch("bool", other)),
+        }
+    }
+}
+
+impl ToWampData for String {
+    fn to_wamp_data(&self) -> WampData {
+        WampData::Str(self.clone())
+    }
+}
+
+impl ToWampData for str {
+    fn to_wamp_data(&self) -> WampData {
+        WampData::Str(self.to_string())
+    }
+}
+
+impl FromWampData for String {
+    fn from_wamp_data(data:&WampData) -> Result<Self, WampError> {
+        match *resolve(data)? {
+            WampData::Str(ref s) => Ok(s.clone()),
+            ref other => Err(mismatch("string", other)),
+        }
+    }
+}
+
+/*
+ * Binary data. A plain Vec<u8> goes through the Vec impls as a list of
+ * integers, wrap it in Bytes to send and receive a WAMP binary value.
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Bytes(pub Vec<u8>);
+
+impl From<Vec<u8>> for Bytes {
+    fn from(bytes:Vec<u8>) -> Self {
+        Bytes(bytes)
+    }
+}
+
+impl From<Bytes> for Vec<u8> {
+    fn from(bytes:Bytes) -> Self {
+        bytes.0
+    }
+}
+
+impl ToWampData for Bytes {
+    fn to_wamp_data(&self) -> WampData {
+        WampData::Bytes(self.0.clone())
+    }
+}
+
+impl FromWampData for Bytes {
+    fn from_wamp_data(data:&WampData) -> Result<Self, WampError> {
+        match *resolve(data)? {
+            WampData::Bytes(ref b) => Ok(Bytes(b.clone())),
+            ref other => Err(mismatch("bytes", other)),
+        }
+    }
+}
+
+impl<T: ToWampData + ?Sized> ToWampData for &T {
+    fn to_wamp_data(&self) -> WampData {
+        (**self).to_wamp_data()
+    }
+}
+
+impl<T: ToWampData + ?Sized> ToWampData for Box<T> {
+    fn to_wamp_data(&self) -> WampData {
+        self.as_ref().to_wamp_data()
+    }
+}
+
+impl<T: FromWampData> FromWampData for Box<T> {
+    fn from_wamp_data(data:&WampData) -> Result<Self, WampError> {
+        T::from_wamp_data(data).map(Box::new)
+    }
+}
+
+impl<T: ToWampData> ToWampData for Option<T> {
+    fn to_wamp_data(&self) -> WampData {
+        match self {
+            Some(v) => v.to_wamp_data(),
+            None => WampData::None,
+        }
+    }
+}
+
+impl<T: FromWampData> FromWampData for Option<T> {
+    fn from_wamp_data(data:&WampData) -> Result<Self, WampError> {
+        match data {
+            WampData::None | WampData::Undefined => Ok(None),
+            data => T::from_wamp_data(data).map(Some),
+        }
+    }
+}
+
+impl<T: ToWampData> ToWampData for Vec<T> {
+    fn to_wamp_data(&self) -> WampData {
+        WampData::Array(Box::new(self.iter().map(|v| v.to_wamp_data()).collect()), 0)
+    }
+}
+
+impl<T: FromWampData> FromWampData for Vec<T> {
+    fn from_wamp_data(data:&WampData) -> Result<Self, WampError> {
+        match *resolve(data)? {
+            WampData::Array(ref a, _) => a.iter().map(T::from_wamp_data).collect(),
+            ref other => Err(mismatch("array", other)),
+        }
+    }
+}
+
+impl<T: ToWampData> ToWampData for HashMap<String, T> {
+    fn to_wamp_data(&self) -> WampData {
+        let hash = self.iter()
+                       .map(|(k, v)| (k.clone(), Box::new(v.to_wamp_data())))
+                       .collect();
+        WampData::Hash(Box::new(hash), 0)
+    }
+}
+
+impl<T: FromWampData> FromWampData for HashMap<String, T> {
+    fn from_wamp_data(data:&WampData) -> Result<Self, WampError> {
+        match *resolve(data)? {
+            WampData::Hash(ref h, _) => {
+                h.iter()
+                 .map(|(k, v)| Ok((k.clone(), T::from_wamp_data(v)?)))
+                 .collect()
+            },
+            ref other => Err(mismatch("dict", other)),
+        }
+    }
+}
+
+/*
+ * Helpers for the code `#[derive(Wamp)]` generates
+ */
+
+/// Prefixes a conversion error with where it happened, e.g. `Point.x`
+pub fn context(e:WampError, owner:&str, name:&str) -> WampError {
+    match e {
+        WampError::ConversionError(msg) => WampError::ConversionError(format!("{}.{}: {}", owner, name, msg)),
+        e => e,
+    }
+}
+
+pub fn expect_hash<'a>(data:&'a WampData, owner:&str) -> Result<&'a WampHash, WampError> {
+    match data {
+        WampData::Hash(h, _) => Ok(h),
+        other => Err(WampError::ConversionError(format!("{}: expected dict, got {:?}", owner, other))),
+    }
+}
+
+pub fn expect_array<'a>(data:&'a WampData, owner:&str) -> Result<&'a WampArray, WampError> {
+    match data {
+        WampData::Array(a, _) => Ok(a),
+        other => Err(WampError::ConversionError(format!("{}: expected array, got {:?}", owner, other))),
+    }
+}
+
+/// A field that has to be present
+pub fn field<T: FromWampData>(value:Option<&WampData>, owner:&str, name:&str) -> Result<T, WampError> {
+    match value {
+        Some(v) => T::from_wamp_data(v).map_err(|e| context(e, owner, name)),
+        None => Err(WampError::ConversionError(format!("{}: missing field {}", owner, name))),
+    }
+}
+
+/// A field that falls back to its Default when missing
+pub fn field_or_default<T: FromWampData + Default>(value:Option<&WampData>, owner:&str, name:&str) -> Result<T, WampError> {
+    match value {
+        Some(v) => T::from_wamp_data(v).map_err(|e| context(e, owner, name)),
+        None => Ok(T::default()),
+    }
+}
+
+/// Variant name and value of an enum in the `"Variant"` or
+/// `{ "Variant": value }` form
+pub fn enum_variant<'a>(data:&'a WampData, owner:&str) -> Result<(&'a str, Option<&'a WampData>), WampError> {
+    match data {
+        WampData::Str(s) => Ok((s, None)),
+        WampData::Hash(h, _) if h.len() == 1 => {
+            let (name, value) = h.iter().next().unwrap();
+            Ok((name, Some(value)))
+        },
+        other => Err(WampError::ConversionError(format!("{}: expected a variant, got {:?}", owner, other))),
+    }
+}
+
+pub fn variant_value<'a>(value:Option<&'a WampData>, owner:&str, variant:&str) -> Result<&'a WampData, WampError> {
+    value.ok_or_else(|| WampError::ConversionError(format!("{}::{} needs a value", owner, variant)))
+}
+
+pub fn unknown_variant(owner:&str, variant:&str) -> WampError {
+    WampError::ConversionError(format!("{}: unknown variant {}", owner, variant))
+}
+
+pub fn variant(name:&str, value:WampData) -> WampData {
+    let mut hash = WampHash::new();
+    hash.insert(name.to_string(), Box::new(value));
+    WampData::Hash(Box::new(hash), 0)
+}
@@ -1,5 +1,7 @@
 use std::fmt;
 
+use crate::client::RpcError;
+
 #[derive(Debug)]
 pub enum WampError {
     NotArray,
@@ -9,7 +11,84 @@ pub enum WampError {
     InvalidField,
 
     ConnectionFailure,
+    HandshakeFailure,
+    MessageTooLarge,
+    NotConnected,
+    AuthenticationFailure,
+    /// The router ABORTed the session, with the reason it gave
+    Aborted(String),
+    Timeout,
+    InvalidMessage,
+    RouterError(String),
+    CallFailed(RpcError),
+    Cancelled,
+    ProgressNotRequested,
+    FeatureNotSupported(String),
+    /// The router handed back a registration that already has a handler
+    AlreadyRegistered(String),
+    InvalidConfiguration(String),
     UnknownRequestID,
+    PayloadError(String),
+    /// A message that doesn't follow the protocol, with what's wrong
+    MalformedMessage(String),
+    DecodeError(DecodeError),
+    /// A value didn't fit the serde type it was converted to or from
+    SerdeError(String),
+    /// WampData that doesn't fit the FromWampData type it was read into
+    ConversionError(String),
+}
+
+impl fmt::Display for WampError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WampError::DecodeError(e) => write!(f, "{}", e),
+            WampError::SerdeError(e) => write!(f, "{}", e),
+            WampError::ConversionError(e) => write!(f, "{}", e),
+            e => write!(f, "{:?}", e),
+        }
+    }
+}
+
+impl std::error::Error for WampError {}
+
+impl From<derive_builder::UninitializedFieldError> for WampError {
+    fn from(e: derive_builder::UninitializedFieldError) -> Self {
+        WampError::InvalidConfiguration(format!("{} must be set", e.field_name()))
+    }
+}
+
+impl From<DecodeError> for WampError {
+    fn from(e: DecodeError) -> Self {
+        WampError::DecodeError(e)
+    }
+}
+
+/*
+ * Where and why a frame could not be decoded
+ */
+#[derive(Debug, Clone)]
+pub struct DecodeError {
+    /// Byte offset into the frame
+    pub position: usize,
+    /// What we were trying to read there
+    pub expected: &'static str,
+    pub reason: String,
+}
+
+impl DecodeError {
+    pub fn new<R: fmt::Display>(position:usize, expected:&'static str, reason:R) -> DecodeError {
+        DecodeError {
+            position,
+            expected,
+            reason: reason.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "expected {} at byte {}: {}", self.expected, self.position, self.reason)
+    }
 }
 
 #[derive(Debug, Clone)]
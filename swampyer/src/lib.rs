@@ -1,12 +1,29 @@
-#![feature(trace_macros)]
 #![allow(unused_imports, unreachable_code)]
 #![allow(unused_variables, dead_code, unused_must_use)]
 
+// Lets generated code name `::swampyer` from inside this crate too
+extern crate self as swampyer;
+
 pub mod serialization;
 pub use crate::serialization::*;
 
+pub mod convert;
+pub use crate::convert::{ToWampData, FromWampData, Bytes};
+pub use swampyer_derive::{Wamp, procedure, interface, subscribe};
+// Used by the code #[procedure] and #[subscribe] generate
+#[doc(hidden)]
+pub use inventory;
+
 mod errors;
 pub use crate::errors::*;
 
+mod message;
+pub use crate::message::*;
+
+#[cfg(feature = "serde")]
+mod wamp_serde;
+#[cfg(feature = "serde")]
+pub use crate::wamp_serde::*;
+
 mod client;
 pub use crate::client::*;
@@ -0,0 +1,427 @@
+use std::convert::TryFrom;
+
+use crate::serialization::WampData;
+use crate::errors::WampError;
+use crate::{WampArray, WampHash};
+
+pub const WAMP_HELLO:u64 = 1;
+pub const WAMP_WELCOME:u64 = 2;
+pub const WAMP_ABORT:u64 = 3;
+pub const WAMP_CHALLENGE:u64 = 4;
+pub const WAMP_AUTHENTICATE:u64 = 5;
+pub const WAMP_GOODBYE:u64 = 6;
+pub const WAMP_ERROR:u64 = 8;
+pub const WAMP_PUBLISH:u64 = 16;
+pub const WAMP_PUBLISHED:u64 = 17;
+pub const WAMP_SUBSCRIBE:u64 = 32;
+pub const WAMP_SUBSCRIBED:u64 = 33;
+pub const WAMP_UNSUBSCRIBE:u64 = 34;
+pub const WAMP_UNSUBSCRIBED:u64 = 35;
+pub const WAMP_EVENT:u64 = 36;
+pub const WAMP_CALL:u64 = 48;
+pub const WAMP_CANCEL:u64 = 49;
+pub const WAMP_RESULTS:u64 = 50;
+pub const WAMP_REGISTER:u64 = 64;
+pub const WAMP_REGISTERED:u64 = 65;
+pub const WAMP_UNREGISTER:u64 = 66;
+pub const WAMP_UNREGISTERED:u64 = 67;
+pub const WAMP_INVOCATION:u64 = 68;
+pub const WAMP_INTERRUPT:u64 = 69;
+pub const WAMP_YIELD:u64 = 70;
+
+/*
+ * Every message of the WAMP protocol. Args and kwargs are empty when a
+ * message leaves them out.
+ */
+#[derive(Debug, Clone)]
+pub enum WampMessage {
+    Hello { realm: String, details: WampHash },
+    Welcome { session_id: u64, details: WampHash },
+    Abort { details: WampHash, reason: String },
+    Challenge { authmethod: String, extra: WampHash },
+    Authenticate { signature: String, extra: WampHash },
+    Goodbye { details: WampHash, reason: String },
+    Error {
+        /// Type code of the message that failed
+        request_type: u64,
+        request_id: u64,
+        details: WampHash,
+        error: String,
+        args: WampArray,
+        kwargs: WampHash,
+    },
+    Publish { request_id: u64, options: WampHash, topic: String, args: WampArray, kwargs: WampHash },
+    Published { request_id: u64, publication_id: u64 },
+    Subscribe { request_id: u64, options: WampHash, topic: String },
+    Subscribed { request_id: u64, subscription_id: u64 },
+    Unsubscribe { request_id: u64, subscription_id: u64 },
+    Unsubscribed { request_id: u64 },
+    Event { subscription_id: u64, publication_id: u64, details: WampHash, args: WampArray, kwargs: WampHash },
+    Call { request_id: u64, options: WampHash, procedure: String, args: WampArray, kwargs: WampHash },
+    Cancel { request_id: u64, options: WampHash },
+    Result { request_id: u64, details: WampHash, args: WampArray, kwargs: WampHash },
+    Register { request_id: u64, options: WampHash, procedure: String },
+    Registered { request_id: u64, registration_id: u64 },
+    Unregister { request_id: u64, registration_id: u64 },
+    Unregistered { request_id: u64 },
+    Invocation { request_id: u64, registration_id: u64, details: WampHash, args: WampArray, kwargs: WampHash },
+    Interrupt { request_id: u64, options: WampHash },
+    Yield { request_id: u64, options: WampHash, args: WampArray, kwargs: WampHash },
+}
+
+/// Name of a message type, for logs and errors
+pub fn message_name(message_type:u64) -> &'static str {
+    match message_type {
+        WAMP_HELLO => "HELLO",
+        WAMP_WELCOME => "WELCOME",
+        WAMP_ABORT => "ABORT",
+        WAMP_CHALLENGE => "CHALLENGE",
+        WAMP_AUTHENTICATE => "AUTHENTICATE",
+        WAMP_GOODBYE => "GOODBYE",
+        WAMP_ERROR => "ERROR",
+        WAMP_PUBLISH => "PUBLISH",
+        WAMP_PUBLISHED => "PUBLISHED",
+        WAMP_SUBSCRIBE => "SUBSCRIBE",
+        WAMP_SUBSCRIBED => "SUBSCRIBED",
+        WAMP_UNSUBSCRIBE => "UNSUBSCRIBE",
+        WAMP_UNSUBSCRIBED => "UNSUBSCRIBED",
+        WAMP_EVENT => "EVENT",
+        WAMP_CALL => "CALL",
+        WAMP_CANCEL => "CANCEL",
+        WAMP_RESULTS => "RESULT",
+        WAMP_REGISTER => "REGISTER",
+        WAMP_REGISTERED => "REGISTERED",
+        WAMP_UNREGISTER => "UNREGISTER",
+        WAMP_UNREGISTERED => "UNREGISTERED",
+        WAMP_INVOCATION => "INVOCATION",
+        WAMP_INTERRUPT => "INTERRUPT",
+        WAMP_YIELD => "YIELD",
+        _ => "UNKNOWN",
+    }
+}
+
+/// Positional arguments from user supplied data. Anything that isn't
+/// already an array is sent as the single argument.
+pub(crate) fn args_from(data:WampData) -> WampArray {
+    match data {
+        WampData::Array(a, _) => *a,
+        WampData::None => WampArray::new(),
+        other => vec![other],
+    }
+}
+
+pub(crate) fn kwargs_from(data:WampData) -> Result<WampHash, WampError> {
+    match data {
+        WampData::Hash(h, _) => Ok(*h),
+        WampData::None => Ok(WampHash::new()),
+        _ => Err(WampError::NotHash),
+    }
+}
+
+/*
+ * Reads the elements of one incoming message, naming the message and
+ * field in any error
+ */
+struct Fields<'a> {
+    message_type: u64,
+    items: &'a [WampData],
+}
+
+impl<'a> Fields<'a> {
+    fn malformed(&self, reason:String) -> WampError {
+        WampError::MalformedMessage(format!("{}: {}", message_name(self.message_type), reason))
+    }
+
+    fn get(&self, i:usize, field:&str) -> Result<&'a WampData, WampError> {
+        self.items.get(i).ok_or_else(|| self.malformed(format!("missing {} at position {}", field, i)))
+    }
+
+    fn id(&self, i:usize, field:&str) -> Result<u64, WampError> {
+        match self.get(i, field)? {
+            WampData::UInt(v) => Ok(*v),
+            other => Err(self.malformed(format!("{} should be an id, got {:?}", field, other))),
+        }
+    }
+
+    fn string(&self, i:usize, field:&str) -> Result<String, WampError> {
+        match self.get(i, field)? {
+            WampData::Str(s) => Ok(s.clone()),
+            other => Err(self.malformed(format!("{} should be a string, got {:?}", field, other))),
+        }
+    }
+
+    fn hash(&self, i:usize, field:&str) -> Result<WampHash, WampError> {
+        match self.get(i, field)? {
+            WampData::Hash(h, _) => Ok(*h.clone()),
+            other => Err(self.malformed(format!("{} should be a dict, got {:?}", field, other))),
+        }
+    }
+
+    fn args(&self, i:usize) -> Result<WampArray, WampError> {
+        match self.items.get(i) {
+            None => Ok(WampArray::new()),
+            Some(WampData::Array(a, _)) => Ok(*a.clone()),
+            Some(other) => Err(self.malformed(format!("args should be a list, got {:?}", other))),
+        }
+    }
+
+    fn kwargs(&self, i:usize) -> Result<WampHash, WampError> {
+        match self.items.get(i) {
+            None => Ok(WampHash::new()),
+            Some(WampData::Hash(h, _)) => Ok(*h.clone()),
+            Some(other) => Err(self.malformed(format!("kwargs should be a dict, got {:?}", other))),
+        }
+    }
+}
+
+fn hash(h:WampHash) -> WampData {
+    WampData::Hash(Box::new(h), 0)
+}
+
+/// Appends args and kwargs, leaving out whatever is empty at the end
+fn push_payload(items:&mut WampArray, args:WampArray, kwargs:WampHash) {
+    if !args.is_empty() || !kwargs.is_empty() {
+        items.push(WampData::Array(Box::new(args), 0));
+    }
+    if !kwargs.is_empty() {
+        items.push(hash(kwargs));
+    }
+}
+
+impl WampMessage {
+    pub fn message_type(&self) -> u64 {
+        match self {
+            WampMessage::Hello { .. } => WAMP_HELLO,
+            WampMessage::Welcome { .. } => WAMP_WELCOME,
+            WampMessage::Abort { .. } => WAMP_ABORT,
+            WampMessage::Challenge { .. } => WAMP_CHALLENGE,
+            WampMessage::Authenticate { .. } => WAMP_AUTHENTICATE,
+            WampMessage::Goodbye { .. } => WAMP_GOODBYE,
+            WampMessage::Error { .. } => WAMP_ERROR,
+            WampMessage::Publish { .. } => WAMP_PUBLISH,
+            WampMessage::Published { .. } => WAMP_PUBLISHED,
+            WampMessage::Subscribe { .. } => WAMP_SUBSCRIBE,
+            WampMessage::Subscribed { .. } => WAMP_SUBSCRIBED,
+            WampMessage::Unsubscribe { .. } => WAMP_UNSUBSCRIBE,
+            WampMessage::Unsubscribed { .. } => WAMP_UNSUBSCRIBED,
+            WampMessage::Event { .. } => WAMP_EVENT,
+            WampMessage::Call { .. } => WAMP_CALL,
+            WampMessage::Cancel { .. } => WAMP_CANCEL,
+            WampMessage::Result { .. } => WAMP_RESULTS,
+            WampMessage::Register { .. } => WAMP_REGISTER,
+            WampMessage::Registered { .. } => WAMP_REGISTERED,
+            WampMessage::Unregister { .. } => WAMP_UNREGISTER,
+            WampMessage::Unregistered { .. } => WAMP_UNREGISTERED,
+            WampMessage::Invocation { .. } => WAMP_INVOCATION,
+            WampMessage::Interrupt { .. } => WAMP_INTERRUPT,
+            WampMessage::Yield { .. } => WAMP_YIELD,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        message_name(self.message_type())
+    }
+
+    /// Request id the message belongs to, for those that have one
+    pub fn request_id(&self) -> Option<u64> {
+        match self {
+            WampMessage::Error { request_id, .. }
+            | WampMessage::Publish { request_id, .. }
+            | WampMessage::Published { request_id, .. }
+            | WampMessage::Subscribe { request_id, .. }
+            | WampMessage::Subscribed { request_id, .. }
+            | WampMessage::Unsubscribe { request_id, .. }
+            | WampMessage::Unsubscribed { request_id }
+            | WampMessage::Call { request_id, .. }
+            | WampMessage::Cancel { request_id, .. }
+            | WampMessage::Result { request_id, .. }
+            | WampMessage::Register { request_id, .. }
+            | WampMessage::Registered { request_id, .. }
+            | WampMessage::Unregister { request_id, .. }
+            | WampMessage::Unregistered { request_id }
+            | WampMessage::Invocation { request_id, .. }
+            | WampMessage::Interrupt { request_id, .. }
+            | WampMessage::Yield { request_id, .. } => Some(*request_id),
+            _ => None,
+        }
+    }
+
+    /// Validates and converts a decoded message
+    pub fn from_data(data:&WampData) -> Result<WampMessage, WampError> {
+        let items = match data {
+            WampData::Array(a, _) => a.as_slice(),
+            other => return Err(WampError::MalformedMessage(format!("message should be a list, got {:?}", other))),
+        };
+        let message_type = match items.first() {
+            Some(WampData::UInt(t)) => *t,
+            other => return Err(WampError::MalformedMessage(format!("message type should be an integer, got {:?}", other))),
+        };
+        let f = Fields { message_type, items };
+
+        let message = match message_type {
+            WAMP_HELLO => WampMessage::Hello { realm: f.string(1, "realm")?, details: f.hash(2, "details")? },
+            WAMP_WELCOME => WampMessage::Welcome { session_id: f.id(1, "session")?, details: f.hash(2, "details")? },
+            WAMP_ABORT => WampMessage::Abort { details: f.hash(1, "details")?, reason: f.string(2, "reason")? },
+            WAMP_CHALLENGE => WampMessage::Challenge { authmethod: f.string(1, "authmethod")?, extra: f.hash(2, "extra")? },
+            WAMP_AUTHENTICATE => WampMessage::Authenticate { signature: f.string(1, "signature")?, extra: f.hash(2, "extra")? },
+            WAMP_GOODBYE => WampMessage::Goodbye { details: f.hash(1, "details")?, reason: f.string(2, "reason")? },
+            WAMP_ERROR => WampMessage::Error {
+                request_type: f.id(1, "request type")?,
+                request_id: f.id(2, "request")?,
+                details: f.hash(3, "details")?,
+                error: f.string(4, "error")?,
+                args: f.args(5)?,
+                kwargs: f.kwargs(6)?,
+            },
+            WAMP_PUBLISH => WampMessage::Publish {
+                request_id: f.id(1, "request")?,
+                options: f.hash(2, "options")?,
+                topic: f.string(3, "topic")?,
+                args: f.args(4)?,
+                kwargs: f.kwargs(5)?,
+            },
+            WAMP_PUBLISHED => WampMessage::Published { request_id: f.id(1, "request")?, publication_id: f.id(2, "publication")? },
+            WAMP_SUBSCRIBE => WampMessage::Subscribe {
+                request_id: f.id(1, "request")?,
+                options: f.hash(2, "options")?,
+                topic: f.string(3, "topic")?,
+            },
+            WAMP_SUBSCRIBED => WampMessage::Subscribed { request_id: f.id(1, "request")?, subscription_id: f.id(2, "subscription")? },
+            WAMP_UNSUBSCRIBE => WampMessage::Unsubscribe { request_id: f.id(1, "request")?, subscription_id: f.id(2, "subscription")? },
+            WAMP_UNSUBSCRIBED => WampMessage::Unsubscribed { request_id: f.id(1, "request")? },
+            WAMP_EVENT => WampMessage::Event {
+                subscription_id: f.id(1, "subscription")?,
+                publication_id: f.id(2, "publication")?,
+                details: f.hash(3, "details")?,
+                args: f.args(4)?,
+                kwargs: f.kwargs(5)?,
+            },
+            WAMP_CALL => WampMessage::Call {
+                request_id: f.id(1, "request")?,
+                options: f.hash(2, "options")?,
+                procedure: f.string(3, "procedure")?,
+                args: f.args(4)?,
+                kwargs: f.kwargs(5)?,
+            },
+            WAMP_CANCEL => WampMessage::Cancel { request_id: f.id(1, "request")?, options: f.hash(2, "options")? },
+            WAMP_RESULTS => WampMessage::Result {
+                request_id: f.id(1, "request")?,
+                details: f.hash(2, "details")?,
+                args: f.args(3)?,
+                kwargs: f.kwargs(4)?,
+            },
+            WAMP_REGISTER => WampMessage::Register {
+                request_id: f.id(1, "request")?,
+                options: f.hash(2, "options")?,
+                procedure: f.string(3, "procedure")?,
+            },
+            WAMP_REGISTERED => WampMessage::Registered { request_id: f.id(1, "request")?, registration_id: f.id(2, "registration")? },
+            WAMP_UNREGISTER => WampMessage::Unregister { request_id: f.id(1, "request")?, registration_id: f.id(2, "registration")? },
+            WAMP_UNREGISTERED => WampMessage::Unregistered { request_id: f.id(1, "request")? },
+            WAMP_INVOCATION => WampMessage::Invocation {
+                request_id: f.id(1, "request")?,
+                registration_id: f.id(2, "registration")?,
+                details: f.hash(3, "details")?,
+                args: f.args(4)?,
+                kwargs: f.kwargs(5)?,
+            },
+            WAMP_INTERRUPT => WampMessage::Interrupt { request_id: f.id(1, "request")?, options: f.hash(2, "options")? },
+            WAMP_YIELD => WampMessage::Yield {
+                request_id: f.id(1, "request")?,
+                options: f.hash(2, "options")?,
+                args: f.args(3)?,
+                kwargs: f.kwargs(4)?,
+            },
+            _ => return Err(WampError::MalformedMessage(format!("unknown message type {}", message_type))),
+        };
+        Ok(message)
+    }
+
+    /// Converts to the list that goes on the wire
+    pub fn into_data(self) -> WampData {
+        let mut items:WampArray = vec![self.message_type().into()];
+        match self {
+            WampMessage::Hello { realm, details } => {
+                items.extend([realm.into(), hash(details)]);
+            },
+            WampMessage::Welcome { session_id, details } => {
+                items.extend([session_id.into(), hash(details)]);
+            },
+            WampMessage::Abort { details, reason } | WampMessage::Goodbye { details, reason } => {
+                items.extend([hash(details), reason.into()]);
+            },
+            WampMessage::Challenge { authmethod, extra } => {
+                items.extend([authmethod.into(), hash(extra)]);
+            },
+            WampMessage::Authenticate { signature, extra } => {
+                items.extend([signature.into(), hash(extra)]);
+            },
+            WampMessage::Error { request_type, request_id, details, error, args, kwargs } => {
+                items.extend([request_type.into(), request_id.into(), hash(details), error.into()]);
+                push_payload(&mut items, args, kwargs);
+            },
+            WampMessage::Publish { request_id, options, topic, args, kwargs } => {
+                items.extend([request_id.into(), hash(options), topic.into()]);
+                push_payload(&mut items, args, kwargs);
+            },
+            WampMessage::Published { request_id, publication_id } => {
+                items.extend([request_id.into(), publication_id.into()]);
+            },
+            WampMessage::Subscribe { request_id, options, topic } => {
+                items.extend([request_id.into(), hash(options), topic.into()]);
+            },
+            WampMessage::Subscribed { request_id, subscription_id }
+            | WampMessage::Unsubscribe { request_id, subscription_id } => {
+                items.extend([request_id.into(), subscription_id.into()]);
+            },
+            WampMessage::Unsubscribed { request_id } | WampMessage::Unregistered { request_id } => {
+                items.push(request_id.into());
+            },
+            WampMessage::Event { subscription_id, publication_id, details, args, kwargs } => {
+                items.extend([subscription_id.into(), publication_id.into(), hash(details)]);
+                push_payload(&mut items, args, kwargs);
+            },
+            WampMessage::Call { request_id, options, procedure, args, kwargs } => {
+                items.extend([request_id.into(), hash(options), procedure.into()]);
+                push_payload(&mut items, args, kwargs);
+            },
+            WampMessage::Cancel { request_id, options } | WampMessage::Interrupt { request_id, options } => {
+                items.extend([request_id.into(), hash(options)]);
+            },
+            WampMessage::Result { request_id, details, args, kwargs } => {
+                items.extend([request_id.into(), hash(details)]);
+                push_payload(&mut items, args, kwargs);
+            },
+            WampMessage::Register { request_id, options, procedure } => {
+                items.extend([request_id.into(), hash(options), procedure.into()]);
+            },
+            WampMessage::Registered { request_id, registration_id }
+            | WampMessage::Unregister { request_id, registration_id } => {
+                items.extend([request_id.into(), registration_id.into()]);
+            },
+            WampMessage::Invocation { request_id, registration_id, details, args, kwargs } => {
+                items.extend([request_id.into(), registration_id.into(), hash(details)]);
+                push_payload(&mut items, args, kwargs);
+            },
+            WampMessage::Yield { request_id, options, args, kwargs } => {
+                items.extend([request_id.into(), hash(options)]);
+                push_payload(&mut items, args, kwargs);
+            },
+        }
+        WampData::Array(Box::new(items), 0)
+    }
+}
+
+impl TryFrom<&WampData> for WampMessage {
+    type Error = WampError;
+
+    fn try_from(data:&WampData) -> Result<WampMessage, WampError> {
+        WampMessage::from_data(data)
+    }
+}
+
+impl From<WampMessage> for WampData {
+    fn from(message:WampMessage) -> WampData {
+        message.into_data()
+    }
+}
@@ -3,6 +3,7 @@
 
 use derive_builder::Builder;
 pub use minicbor::{Decoder, Encoder, encode, data::Type};
+use minicbor::data::{Int, Tag};
 pub use minicbor_derive::{Encode, Decode};
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -11,6 +12,12 @@ use crate::errors::*;
 
 use std::mem;
 
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+
+/// How deep arrays and maps may nest before a frame is rejected
+const MAX_NESTING:usize = 128;
+
 /*
  * Trait for allowing encode and decode
  */
@@ -50,6 +57,56 @@ impl encode::Write for WampWrite {
     }
 }
 
+/*
+ * Wire formats we can speak to the router
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Serializer {
+    Json,
+    #[default]
+    Cbor,
+}
+
+impl Serializer {
+    /// Serializer identifier used in the rawsocket handshake
+    pub fn rawsocket_id(&self) -> u8 {
+        match self {
+            Serializer::Json => 1,
+            Serializer::Cbor => 3,
+        }
+    }
+
+    /// Name as used in WAMP options such as `ppt_serializer`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Serializer::Json => "json",
+            Serializer::Cbor => "cbor",
+        }
+    }
+
+    pub fn from_name(name:&str) -> Option<Serializer> {
+        match name {
+            "json" => Some(Serializer::Json),
+            "cbor" => Some(Serializer::Cbor),
+            _ => None,
+        }
+    }
+
+    pub fn encode(&self, data:&WampData) -> Vec<u8> {
+        match self {
+            Serializer::Json => data.to_json().to_string().into_bytes(),
+            Serializer::Cbor => data.to_vec(),
+        }
+    }
+
+    pub fn decode(&self, data:Vec<u8>) -> Result<Box<WampData>, WampError> {
+        match self {
+            Serializer::Json => WampData::from_json_slice(&data),
+            Serializer::Cbor => WampData::from_slice(data),
+        }
+    }
+}
+
 /*
  * Recursive data structure for WAMP calls
  */
@@ -61,14 +118,65 @@ pub enum WampData {
     Float(f64),
     Int(i64),
     UInt(u64),
+    /// Integers beyond i64 and u64. CBOR goes down to -2^64.
+    BigInt(i128),
     Bool(bool),
     Str(String),
+    Bytes(Vec<u8>),
     Array(Box<WampArray>, usize),
     Hash(Box<WampHash>, usize),
+    /// CBOR tagged value such as a timestamp (1) or bignum (2 and 3)
+    Tagged(u64, Box<WampData>),
+    /// CBOR simple value without a meaning of its own
+    Simple(u8),
     Serializable(Arc<dyn WampSerializable + Send + Sync>),
+    /// CBOR undefined, which JSON only knows as null
+    Undefined,
     None,
 }
 
+/// The tag's number, which minicbor keeps to itself
+/// Big endian bytes of a bignum without leading zeros
+fn bignum_bytes(n:u128) -> Vec<u8> {
+    let bytes = n.to_be_bytes();
+    let start = bytes.iter().position(|b| *b != 0).unwrap_or(bytes.len());
+    bytes[start..].to_vec()
+}
+
+/// Value of a bignum's bytes, if it fits in a BigInt
+fn bignum_value(negative:bool, bytes:&[u8]) -> Option<i128> {
+    let start = bytes.iter().position(|b| *b != 0).unwrap_or(bytes.len());
+    let bytes = &bytes[start..];
+    if bytes.len() > 16 {
+        return None;
+    }
+    let mut buf = [0u8; 16];
+    buf[16 - bytes.len()..].copy_from_slice(bytes);
+    let n = i128::try_from(u128::from_be_bytes(buf)).ok()?;
+    if negative { Some(-1 - n) } else { Some(n) }
+}
+
+fn tag_number(tag:Tag) -> u64 {
+    match tag {
+        Tag::DateTime => 0x00,
+        Tag::Timestamp => 0x01,
+        Tag::PosBignum => 0x02,
+        Tag::NegBignum => 0x03,
+        Tag::Decimal => 0x04,
+        Tag::Bigfloat => 0x05,
+        Tag::ToBase64Url => 0x15,
+        Tag::ToBase64 => 0x16,
+        Tag::ToBase16 => 0x17,
+        Tag::Cbor => 0x18,
+        Tag::Uri => 0x20,
+        Tag::Base64Url => 0x21,
+        Tag::Base64 => 0x22,
+        Tag::Regex => 0x23,
+        Tag::Mime => 0x24,
+        Tag::Unassigned(n) => n,
+    }
+}
+
 impl <'a> WampData {
     pub fn as_u64 (&self) -> Result<u64, WampError> {
         match self {
@@ -77,9 +185,19 @@ impl <'a> WampData {
         }
     }
 
+    pub fn as_bytes (&self) -> Result<&[u8], WampError> {
+        match self {
+            WampData::Bytes(b) => Ok(b),
+            _ => Err(WampError::IncorrectElementType),
+        }
+    }
+
     pub fn from_slice(data:Vec<u8>) -> Result<Box<WampData>, WampError> {
         let mut decoder = Box::new(Decoder::new(&data));
-        let desered = Box::new(WampData::deserialize_with(&mut decoder));
+        let desered = Box::new(WampData::deserialize_with(&mut decoder)?);
+        if decoder.position() != data.len() {
+            return Err(DecodeError::new(decoder.position(), "end of data", "trailing bytes after the value").into());
+        }
         Ok(desered)
     }
 
@@ -96,9 +214,24 @@ impl <'a> WampData {
             WampData::Float(f) => { encoder.f64(*f); },
             WampData::Int(i) => { encoder.i64(*i); },
             WampData::UInt(u) => { encoder.u64(*u); },
+            WampData::BigInt(i) => {
+                match Int::try_from(*i) {
+                    Ok(i) => { encoder.int(i); },
+                    // Past 64 bits CBOR needs a bignum, which stores -1 - n
+                    // for negative numbers
+                    Err(_) if *i > 0 => {
+                        encoder.tag(Tag::PosBignum);
+                        encoder.bytes(&bignum_bytes(*i as u128));
+                    },
+                    Err(_) => {
+                        encoder.tag(Tag::NegBignum);
+                        encoder.bytes(&bignum_bytes((-1 - *i) as u128));
+                    },
+                }
+            },
             WampData::Bool(b) => { encoder.bool(*b); },
             WampData::Str(s) => { encoder.str(s); },
-            // WampData::Str(s) => { encoder.bytes(s.as_bytes()); },
+            WampData::Bytes(b) => { encoder.bytes(b); },
             WampData::Array(a, _) => {
                 encoder.begin_array();
                 for v in a.iter() {
@@ -114,6 +247,13 @@ impl <'a> WampData {
                 }
                 encoder.end();
             },
+            WampData::Tagged(tag, v) => {
+                // Unassigned carries any number through to the encoder
+                encoder.tag(Tag::Unassigned(*tag));
+                v.serialize_with(encoder);
+            },
+            WampData::Simple(s) => { encoder.simple(*s); },
+            WampData::Undefined => { encoder.undefined(); },
             WampData::None => { encoder.null(); },
             WampData::Serializable(data) => {
                 data.encode(encoder);
@@ -121,118 +261,230 @@ impl <'a> WampData {
         };
     }
 
-    pub fn deserialize_with(decoder:&mut Box<Decoder>) -> Self {
-        match decoder.datatype() {
-            Ok(dt) => {
-                match dt {
-                    Type::Bool => { WampData::Bool(decoder.bool().unwrap().into()) },
-                    Type::U8 => { WampData::UInt(decoder.u8().unwrap().into()) },
-                    Type::U16 => { WampData::UInt(decoder.u16().unwrap().into()) },
-                    Type::U32 => { WampData::UInt(decoder.u32().unwrap().into()) },
-                    Type::U64 => { WampData::UInt(decoder.u64().unwrap().into()) },
-                    Type::I16 => { WampData::Int(decoder.i16().unwrap().into()) },
-                    Type::I32 => { WampData::Int(decoder.i32().unwrap().into()) },
-                    Type::I64 => { WampData::Int(decoder.i64().unwrap().into()) },
-                    Type::F16 => { WampData::Float(decoder.f32().unwrap().into()) },
-                    Type::F32 => { WampData::Float(decoder.f32().unwrap().into()) },
-                    Type::F64 => { WampData::Float(decoder.f64().unwrap().into()) },
-                    Type::String => {
-                        WampData::Str(decoder.str().unwrap().into())
+    pub fn from_json_slice(data:&[u8]) -> Result<Box<WampData>, WampError> {
+        match serde_json::from_slice(data) {
+            Ok(value) => Ok(Box::new(WampData::from_json(&value))),
+            // Frames are a single line so the column is the byte offset
+            Err(e) => Err(DecodeError::new(e.column().saturating_sub(1), "JSON value", e).into()),
+        }
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        use serde_json::Value;
+        match self {
+            WampData::Float(f) => {
+                serde_json::Number::from_f64(*f).map_or(Value::Null, Value::Number)
+            },
+            WampData::Int(i) => Value::from(*i),
+            WampData::UInt(u) => Value::from(*u),
+            // Only numbers past 64 bits lose precision, JSON has nothing
+            // bigger to put them in
+            WampData::BigInt(i) => {
+                match (i64::try_from(*i), u64::try_from(*i)) {
+                    (Ok(i), _) => Value::from(i),
+                    (_, Ok(u)) => Value::from(u),
+                    _ => Value::from(*i as f64),
+                }
+            },
+            WampData::Bool(b) => Value::Bool(*b),
+            WampData::Str(s) => Value::String(s.clone()),
+            // WAMP's JSON convention for binary: a NUL then base64
+            WampData::Bytes(b) => Value::String(format!("\0{}", BASE64.encode(b))),
+            WampData::Array(a, _) => {
+                Value::Array(a.iter().map(|v| v.to_json()).collect())
+            },
+            WampData::Hash(h, _) => {
+                Value::Object(h.iter().map(|(k, v)| (k.clone(), v.to_json())).collect())
+            },
+            // JSON has no tags so only the value makes it across
+            WampData::Tagged(_, v) => v.to_json(),
+            WampData::Simple(_) | WampData::Undefined | WampData::None => Value::Null,
+            // Custom types only know how to write CBOR so we round trip
+            // them through it to get at their structure
+            WampData::Serializable(_) => {
+                match WampData::from_slice(self.to_vec()) {
+                    Ok(data) => data.to_json(),
+                    Err(_) => Value::Null,
+                }
+            },
+        }
+    }
+
+    pub fn from_json(value:&serde_json::Value) -> Self {
+        use serde_json::Value;
+        match value {
+            Value::Null => WampData::None,
+            Value::Bool(b) => WampData::Bool(*b),
+            Value::Number(n) => {
+                if let Some(u) = n.as_u64() {
+                    WampData::UInt(u)
+                }
+                else if let Some(i) = n.as_i64() {
+                    WampData::Int(i)
+                }
+                else {
+                    WampData::Float(n.as_f64().unwrap_or_default())
+                }
+            },
+            Value::String(s) => {
+                match s.strip_prefix('\0').map(|encoded| BASE64.decode(encoded)) {
+                    Some(Ok(b)) => WampData::Bytes(b),
+                    _ => WampData::Str(s.clone()),
+                }
+            },
+            Value::Array(a) => {
+                WampData::Array(Box::new(a.iter().map(WampData::from_json).collect()), 0)
+            },
+            Value::Object(o) => {
+                let hs = o.iter()
+                          .map(|(k, v)| (k.clone(), Box::new(WampData::from_json(v))))
+                          .collect();
+                WampData::Hash(Box::new(hs), 0)
+            },
+        }
+    }
+
+    pub fn deserialize_with(decoder:&mut Box<Decoder>) -> Result<Self, DecodeError> {
+        WampData::deserialize_nested(decoder, 0)
+    }
+
+    fn deserialize_nested(decoder:&mut Box<Decoder>, depth:usize) -> Result<Self, DecodeError> {
+        let position = decoder.position();
+        let fail = |expected:&'static str| move |e:minicbor::decode::Error| DecodeError::new(position, expected, e);
+        if depth > MAX_NESTING {
+            return Err(DecodeError::new(position, "a value", "nested too deeply"));
+        }
+
+        let dt = decoder.datatype().map_err(fail("a value"))?;
+        let data = match dt {
+            Type::Bool => { WampData::Bool(decoder.bool().map_err(fail("bool"))?) },
+            Type::Null => {
+                decoder.null().map_err(fail("null"))?;
+                WampData::None
+            },
+            Type::Undefined => {
+                decoder.undefined().map_err(fail("undefined"))?;
+                WampData::Undefined
+            },
+            Type::Simple => { WampData::Simple(decoder.simple().map_err(fail("simple value"))?) },
+            Type::U8 => { WampData::UInt(decoder.u8().map_err(fail("u8"))?.into()) },
+            Type::U16 => { WampData::UInt(decoder.u16().map_err(fail("u16"))?.into()) },
+            Type::U32 => { WampData::UInt(decoder.u32().map_err(fail("u32"))?.into()) },
+            Type::U64 => { WampData::UInt(decoder.u64().map_err(fail("u64"))?) },
+            Type::I8 => { WampData::Int(decoder.i8().map_err(fail("i8"))?.into()) },
+            Type::I16 => { WampData::Int(decoder.i16().map_err(fail("i16"))?.into()) },
+            Type::I32 => { WampData::Int(decoder.i32().map_err(fail("i32"))?.into()) },
+            Type::I64 => { WampData::Int(decoder.i64().map_err(fail("i64"))?) },
+            Type::Int => { WampData::BigInt(decoder.int().map_err(fail("integer"))?.into()) },
+            Type::F16 => { WampData::Float(decoder.f16().map_err(fail("f16"))?.into()) },
+            Type::F32 => { WampData::Float(decoder.f32().map_err(fail("f32"))?.into()) },
+            Type::F64 => { WampData::Float(decoder.f64().map_err(fail("f64"))?) },
+            Type::String => {
+                WampData::Str(decoder.str().map_err(fail("string"))?.into())
+            },
+            Type::StringIndef => {
+                let mut s = String::new();
+                for chunk in decoder.str_iter().map_err(fail("string"))? {
+                    s.push_str(chunk.map_err(fail("string chunk"))?);
+                }
+                WampData::Str(s)
+            },
+            Type::Bytes => {
+                WampData::Bytes(decoder.bytes().map_err(fail("bytes"))?.to_vec())
+            },
+            Type::BytesIndef => {
+                let mut b = Vec::new();
+                for chunk in decoder.bytes_iter().map_err(fail("bytes"))? {
+                    b.extend_from_slice(chunk.map_err(fail("bytes chunk"))?);
+                }
+                WampData::Bytes(b)
+            },
+            Type::Tag => {
+                let tag = tag_number(decoder.tag().map_err(fail("tag"))?);
+                let value = WampData::deserialize_nested(decoder, depth + 1)?;
+                match (tag, &value) {
+                    (2 | 3, WampData::Bytes(b)) => {
+                        match bignum_value(tag == 3, b) {
+                            Some(i) => WampData::BigInt(i),
+                            None => WampData::Tagged(tag, Box::new(value)),
+                        }
                     },
-                    Type::Array => {
-                        let position = decoder.position();
-                        match decoder.array() {
-                            Ok(s) => {
-                                match s {
-                                    Some(v) => {
-                                        let mut ar = Box::new(WampArray::new());
-                                        for i in 0..v {
-                                            let ar_element = WampData::deserialize_with(decoder);
-                                            ar.push(ar_element);
-                                        }
-                                        WampData::Array(ar, position)
-                                    },
-                                    _ => WampData::None
-                                }
-                            },
-                            Err(e) => {
-                                WampData::None
-                            }
+                    _ => WampData::Tagged(tag, Box::new(value)),
+                }
+            },
+            Type::Array | Type::ArrayIndef => {
+                let mut ar = Box::new(WampArray::new());
+                match decoder.array().map_err(fail("array"))? {
+                    Some(len) => {
+                        for _ in 0..len {
+                            ar.push(WampData::deserialize_nested(decoder, depth + 1)?);
                         }
                     },
-                    Type::ArrayIndef => {
-                        let position = decoder.position();
-                        let mut ar = Box::new(WampArray::new());
-                        decoder.array();
-                        loop {
-                            if decoder.datatype().unwrap() == Type::Break {
-                                break;
-                            };
-                            let w = WampData::deserialize_with(decoder);
-                            ar.push(w);
+                    None => {
+                        while !WampData::at_break(decoder)? {
+                            ar.push(WampData::deserialize_nested(decoder, depth + 1)?);
                         }
-                        WampData::Array(ar, position)
-                    }
-
-                    Type::MapIndef => {
-                        let position = decoder.position();
-                        let mut hs = Box::new(WampHash::new());
-                        decoder.map();
-                        loop {
-                            if decoder.datatype().unwrap() == Type::Break {
-                                break;
-                            };
-                            hs.insert(
-                                decoder.str().unwrap().into(),
-                                Box::new(WampData::deserialize_with(decoder))
-                            );
+                    },
+                }
+                WampData::Array(ar, position)
+            },
+            Type::Map | Type::MapIndef => {
+                let mut hs = Box::new(WampHash::new());
+                match decoder.map().map_err(fail("map"))? {
+                    Some(len) => {
+                        for _ in 0..len {
+                            let key = WampData::map_key(decoder, depth + 1)?;
+                            hs.insert(key, Box::new(WampData::deserialize_nested(decoder, depth + 1)?));
                         }
-                        WampData::Hash(hs, position)
                     },
-
-                    Type::Map => {
-                        let position = decoder.position();
-                        match decoder.map() {
-                            Ok(s) => {
-                                match s {
-                                    Some(v) => {
-                                        let mut hs = Box::new(WampHash::new());
-                                        for i in 0..v {
-                                            hs.insert(
-                                                decoder.str().unwrap().into(),
-                                                Box::new(WampData::deserialize_with(decoder))
-                                            );
-                                        }
-                                        WampData::Hash(hs, position)
-                                    }
-                                    _ => WampData::None
-                                }
-                            }
-                            _ => WampData::None
+                    None => {
+                        while !WampData::at_break(decoder)? {
+                            let key = WampData::map_key(decoder, depth + 1)?;
+                            hs.insert(key, Box::new(WampData::deserialize_nested(decoder, depth + 1)?));
                         }
                     },
-                    _ => {
-                        println!("SKIPPING {:?}", dt);
-                        decoder.skip();
-                        WampData::None
-                    }
                 }
+                WampData::Hash(hs, position)
+            },
+            Type::Break | Type::Unknown(_) => {
+                return Err(DecodeError::new(position, "a value", format!("unexpected {}", dt)));
             },
-            // Should be an error
-            Err(err) => WampData::None
+        };
+        Ok(data)
+    }
+
+    /// True, having stepped over it, when the decoder sits on the break
+    /// that closes an indefinite length array or map
+    fn at_break(decoder:&mut Box<Decoder>) -> Result<bool, DecodeError> {
+        let position = decoder.position();
+        match decoder.datatype() {
+            Ok(Type::Break) => {
+                decoder.set_position(position + 1);
+                Ok(true)
+            },
+            Ok(_) => Ok(false),
+            Err(e) => Err(DecodeError::new(position, "a value or break", e)),
+        }
+    }
+
+    fn map_key(decoder:&mut Box<Decoder>, depth:usize) -> Result<String, DecodeError> {
+        let position = decoder.position();
+        match WampData::deserialize_nested(decoder, depth)? {
+            WampData::Str(key) => Ok(key),
+            other => Err(DecodeError::new(position, "string map key", format!("got {:?}", other))),
         }
     }
 
     pub fn h(&self, i:&str) -> Result<&WampData, WampError> {
         match self {
-            WampData::Hash(h, _) => Ok(&h[i]),
-            _ => Err(WampError::InvalidField),
+            WampData::Hash(h, _) => h.get(i).map(|v| v.as_ref()).ok_or(WampError::InvalidField),
+            _ => Err(WampError::NotHash),
         }
     }
 
     pub fn a(&self, i:usize) -> Result<&WampData, WampError> {
         match self {
-            WampData::Array(a, _) => Ok(&a[i]),
+            WampData::Array(a, _) => a.get(i).ok_or(WampError::IncorrectElementCount),
             _ => Err(WampError::NotArray),
         }
     }
@@ -258,13 +510,13 @@ impl <'a> WampData {
 
 impl From<u64> for WampData {
     fn from(i:u64) -> Self {
-        WampData::UInt(i.into())
+        WampData::UInt(i)
     }
 }
 
 impl From<i64> for WampData {
     fn from(i:i64) -> Self {
-        WampData::Int(i.into())
+        WampData::Int(i)
     }
 }
 
@@ -312,13 +564,13 @@ impl From<f32> for WampData {
 
 impl From<f64> for WampData {
     fn from(i:f64) -> Self {
-        WampData::Float(i.into())
+        WampData::Float(i)
     }
 }
 
 impl From<bool> for WampData {
     fn from(i:bool) -> Self {
-        WampData::Bool(i.into())
+        WampData::Bool(i)
     }
 }
 
@@ -330,77 +582,185 @@ impl From<&str> for WampData {
 
 impl From<String> for WampData {
     fn from(i:String) -> Self {
-        WampData::Str(i.into())
+        WampData::Str(i)
+    }
+}
+
+impl From<Vec<u8>> for WampData {
+    fn from(i:Vec<u8>) -> Self {
+        WampData::Bytes(i)
     }
 }
 
+impl From<&[u8]> for WampData {
+    fn from(i:&[u8]) -> Self {
+        WampData::Bytes(i.to_vec())
+    }
+}
+
+impl From<WampArray> for WampData {
+    fn from(i:WampArray) -> Self {
+        WampData::Array(Box::new(i), 0)
+    }
+}
+
+impl From<WampHash> for WampData {
+    fn from(i:WampHash) -> Self {
+        WampData::Hash(Box::new(i), 0)
+    }
+}
+
+/*
+ * JSON-like literals for WampData
+ *
+ *   wdata!({ "name": name, key: [1, 2.5, null, (a + b)], ..defaults })
+ *
+ * Values are `null`, nested arrays and dicts or any expression that
+ * converts into WampData. Keys are any expression with to_string(),
+ * so a variable holding the key works too. `..x` spreads a WampArray
+ * into an array or a WampHash into a dict. Trailing commas are fine.
+ */
 #[macro_export]
 macro_rules! wdata {
 
     // ARRAY
-    ( [] ) => {
-        {
-        let array = Box::new(WampArray::new());
-        WampData::Array(array, 0)
-        }
-    };
+    ( [] ) => { $crate::WampData::from($crate::WampArray::new()) };
 
-    ( [  $( $v:tt ),* ] ) => {
+    // Lists of single tokens, like the literals used most of the time,
+    // are built without recursing once per element
+    ( [ $( $v:tt ),* $(,)? ] ) => {
         {
-        let mut array = Box::new(WampArray::with_capacity(10));
+        let mut array = $crate::WampArray::new();
         $(
-            let data = wdata!($v);
-            array.push(data);
+            array.push($crate::wdata!($v));
         )*
-        WampData::Array(array, 0)
+        $crate::WampData::Array(Box::new(array), 0)
         }
     };
 
-    ( [  $( $v:tt ),* , ] ) => {
+    ( [ $( $tt:tt )* ] ) => {
         {
-        let mut array = Box::new(WampArray::new());
-        $(
-            array.push(wdata!($v));
-        )*
-        WampData::Array(array, 0)
+        let mut array = $crate::WampArray::new();
+        $crate::wdata!(@array array $( $tt )*);
+        $crate::WampData::Array(Box::new(array), 0)
         }
     };
 
     // DICT
-    ( { $( $k:tt: $v:tt ),* , } ) => {
+    ( {} ) => { $crate::WampData::from($crate::WampHash::new()) };
+
+    ( { $( $k:tt : $v:tt ),* $(,)? } ) => {
         {
-        let mut hash = Box::new(WampHash::new());
+        let mut hash = $crate::WampHash::new();
         $(
-            let hash_key = wdata!(@dict_key $k);
-            hash.insert(
-                hash_key,
-                Box::new(wdata!($v))
-            );
+            hash.insert($crate::wdata!(@key $k), Box::new($crate::wdata!($v)));
         )*
-        WampData::Hash(hash, 0)
+        $crate::WampData::Hash(Box::new(hash), 0)
         }
     };
 
-    ( { $( $k:tt: $v:tt ),* } ) => {
+    ( { $( $tt:tt )* } ) => {
         {
-        let hash = Box::new(WampHash::new());
-        $(
-            let hash_key = wdata!(@dict_key $k);
-            hash.insert(
-                hash_key,
-                Box::new(wdata!($v))
-            );
-        )*
-        WampData::Hash(hash, 0)
+        let mut hash = $crate::WampHash::new();
+        $crate::wdata!(@dict hash $( $tt )*);
+        $crate::WampData::Hash(Box::new(hash), 0)
         }
     };
 
+    // ARRAY ELEMENTS
+    ( @array $array:ident ) => {};
+
+    ( @array $array:ident , $( $rest:tt )* ) => {
+        $crate::wdata!(@array $array $( $rest )*)
+    };
+
+    ( @array $array:ident .. $spread:expr , $( $rest:tt )* ) => {
+        $array.extend(::std::iter::IntoIterator::into_iter($spread).map(::std::convert::Into::<$crate::WampData>::into));
+        $crate::wdata!(@array $array $( $rest )*)
+    };
+
+    ( @array $array:ident .. $spread:expr ) => {
+        $array.extend(::std::iter::IntoIterator::into_iter($spread).map(::std::convert::Into::<$crate::WampData>::into));
+    };
+
+    ( @array $array:ident $( $rest:tt )+ ) => {
+        $crate::wdata!(@element $array [] $( $rest )+)
+    };
+
+    // One element, collected token by token up to the next comma
+    ( @element $array:ident [ $( $v:tt )+ ] , $( $rest:tt )* ) => {
+        $array.push($crate::wdata!($( $v )+));
+        $crate::wdata!(@array $array $( $rest )*)
+    };
+
+    ( @element $array:ident [ $( $v:tt )+ ] ) => {
+        $array.push($crate::wdata!($( $v )+));
+    };
+
+    ( @element $array:ident [ $( $v:tt )* ] $next:tt $( $rest:tt )* ) => {
+        $crate::wdata!(@element $array [ $( $v )* $next ] $( $rest )*)
+    };
+
+    // DICT ENTRIES
+    ( @dict $hash:ident ) => {};
+
+    ( @dict $hash:ident , $( $rest:tt )* ) => {
+        $crate::wdata!(@dict $hash $( $rest )*)
+    };
+
+    ( @dict $hash:ident .. $spread:expr , $( $rest:tt )* ) => {
+        $hash.extend($spread);
+        $crate::wdata!(@dict $hash $( $rest )*)
+    };
+
+    ( @dict $hash:ident .. $spread:expr ) => {
+        $hash.extend($spread);
+    };
+
+    ( @dict $hash:ident $( $rest:tt )+ ) => {
+        $crate::wdata!(@entry $hash [] $( $rest )+)
+    };
+
+    // Key tokens up to the colon
+    ( @entry $hash:ident [ $( $k:tt )+ ] : $( $rest:tt )* ) => {
+        $crate::wdata!(@value $hash [ $( $k )+ ] [] $( $rest )*)
+    };
+
+    ( @entry $hash:ident [ $( $k:tt )* ] $next:tt $( $rest:tt )* ) => {
+        $crate::wdata!(@entry $hash [ $( $k )* $next ] $( $rest )*)
+    };
+
+    ( @entry $hash:ident [ $( $k:tt )+ ] ) => {
+        compile_error!(concat!("wdata!: missing value for key `", stringify!($( $k )+), "`"));
+    };
+
+    // Value tokens up to the next comma
+    ( @value $hash:ident [ $( $k:tt )+ ] [ $( $v:tt )+ ] , $( $rest:tt )* ) => {
+        $hash.insert($crate::wdata!(@key ($( $k )+)), Box::new($crate::wdata!($( $v )+)));
+        $crate::wdata!(@dict $hash $( $rest )*)
+    };
+
+    ( @value $hash:ident [ $( $k:tt )+ ] [ $( $v:tt )+ ] ) => {
+        $hash.insert($crate::wdata!(@key ($( $k )+)), Box::new($crate::wdata!($( $v )+)));
+    };
+
+    ( @value $hash:ident [ $( $k:tt )+ ] [] $( , $( $rest:tt )* )? ) => {
+        compile_error!(concat!("wdata!: missing value for key `", stringify!($( $k )+), "`"));
+    };
+
+    ( @value $hash:ident [ $( $k:tt )+ ] [ $( $v:tt )* ] $next:tt $( $rest:tt )* ) => {
+        $crate::wdata!(@value $hash [ $( $k )+ ] [ $( $v )* $next ] $( $rest )*)
+    };
+
     // DICT KEY
-    ( @dict_key $x:expr ) => {
-        $x.into()
+    ( @key $k:expr ) => {
+        ::std::string::ToString::to_string(&$k)
     };
 
-    ( $x:expr ) => { $x.into() };
+    // VALUES
+    ( null ) => { $crate::WampData::None };
+
+    ( $x:expr ) => { ::std::convert::Into::<$crate::WampData>::into($x) };
 
-    () => {};
+    () => { $crate::WampData::None };
 }
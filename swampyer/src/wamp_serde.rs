@@ -0,0 +1,476 @@
+use std::fmt;
+
+use serde::de::{self, Deserialize, DeserializeOwned, Deserializer, IntoDeserializer, Visitor};
+use serde::de::value::{MapDeserializer, SeqDeserializer};
+use serde::ser::{self, Serialize, Serializer};
+
+use crate::serialization::{WampData, WampHash, WampArray};
+use crate::WampError;
+
+/*
+ * Moving any serde type in and out of WampData
+ */
+
+/// Turns a serde value into WampData. Structs and maps become hashes,
+/// sequences and tuples become arrays.
+pub fn to_wamp_data<T: Serialize + ?Sized>(value:&T) -> Result<WampData, WampError> {
+    value.serialize(WampDataSerializer)
+}
+
+pub fn from_wamp_data<T: DeserializeOwned>(data:WampData) -> Result<T, WampError> {
+    T::deserialize(data)
+}
+
+impl ser::Error for WampError {
+    fn custom<T: fmt::Display>(msg:T) -> Self {
+        WampError::SerdeError(msg.to_string())
+    }
+}
+
+impl de::Error for WampError {
+    fn custom<T: fmt::Display>(msg:T) -> Self {
+        WampError::SerdeError(msg.to_string())
+    }
+}
+
+impl Serialize for WampData {
+    fn serialize<S: Serializer>(&self, serializer:S) -> Result<S::Ok, S::Error> {
+        use ser::{SerializeMap, SerializeSeq};
+
+        match self {
+            WampData::Float(f) => serializer.serialize_f64(*f),
+            WampData::Int(i) => serializer.serialize_i64(*i),
+            WampData::UInt(u) => serializer.serialize_u64(*u),
+            WampData::BigInt(i) => serializer.serialize_i128(*i),
+            WampData::Bool(b) => serializer.serialize_bool(*b),
+            WampData::Str(s) => serializer.serialize_str(s),
+            WampData::Bytes(b) => serializer.serialize_bytes(b),
+            WampData::Array(a, _) => {
+                let mut seq = serializer.serialize_seq(Some(a.len()))?;
+                for v in a.iter() {
+                    seq.serialize_element(v)?;
+                }
+                seq.end()
+            },
+            WampData::Hash(h, _) => {
+                let mut map = serializer.serialize_map(Some(h.len()))?;
+                for (k, v) in h.iter() {
+                    map.serialize_entry(k, v.as_ref())?;
+                }
+                map.end()
+            },
+            // serde has no notion of tags so the value stands alone
+            WampData::Tagged(_, v) => v.serialize(serializer),
+            WampData::Simple(s) => serializer.serialize_u8(*s),
+            WampData::Serializable(_) => {
+                match WampData::from_slice(self.to_vec()) {
+                    Ok(data) => data.serialize(serializer),
+                    Err(e) => Err(ser::Error::custom(format!("{:?}", e))),
+                }
+            },
+            WampData::Undefined | WampData::None => serializer.serialize_unit(),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for WampData {
+    fn deserialize<D: Deserializer<'de>>(deserializer:D) -> Result<WampData, D::Error> {
+        deserializer.deserialize_any(WampDataVisitor)
+    }
+}
+
+struct WampDataVisitor;
+
+impl<'de> Visitor<'de> for WampDataVisitor {
+    type Value = WampData;
+
+    fn expecting(&self, f:&mut fmt::Formatter) -> fmt::Result {
+        f.write_str("any WAMP value")
+    }
+
+    fn visit_bool<E>(self, v:bool) -> Result<WampData, E> { Ok(WampData::Bool(v)) }
+    fn visit_i64<E>(self, v:i64) -> Result<WampData, E> { Ok(WampData::Int(v)) }
+    fn visit_u64<E>(self, v:u64) -> Result<WampData, E> { Ok(WampData::UInt(v)) }
+    fn visit_i128<E>(self, v:i128) -> Result<WampData, E> { Ok(WampData::BigInt(v)) }
+    fn visit_u128<E>(self, v:u128) -> Result<WampData, E> {
+        match u64::try_from(v) {
+            Ok(u) => Ok(WampData::UInt(u)),
+            Err(_) => Ok(WampData::Float(v as f64)),
+        }
+    }
+    fn visit_f64<E>(self, v:f64) -> Result<WampData, E> { Ok(WampData::Float(v)) }
+    fn visit_str<E>(self, v:&str) -> Result<WampData, E> { Ok(WampData::Str(v.to_string())) }
+    fn visit_string<E>(self, v:String) -> Result<WampData, E> { Ok(WampData::Str(v)) }
+    fn visit_bytes<E>(self, v:&[u8]) -> Result<WampData, E> { Ok(WampData::Bytes(v.to_vec())) }
+    fn visit_byte_buf<E>(self, v:Vec<u8>) -> Result<WampData, E> { Ok(WampData::Bytes(v)) }
+    fn visit_unit<E>(self) -> Result<WampData, E> { Ok(WampData::None) }
+    fn visit_none<E>(self) -> Result<WampData, E> { Ok(WampData::None) }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer:D) -> Result<WampData, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq:A) -> Result<WampData, A::Error> {
+        let mut array = WampArray::new();
+        while let Some(v) = seq.next_element()? {
+            array.push(v);
+        }
+        Ok(WampData::Array(Box::new(array), 0))
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(self, mut map:A) -> Result<WampData, A::Error> {
+        let mut hash = WampHash::new();
+        while let Some((k, v)) = map.next_entry::<String, WampData>()? {
+            hash.insert(k, Box::new(v));
+        }
+        Ok(WampData::Hash(Box::new(hash), 0))
+    }
+}
+
+/*
+ * serde Serializer that builds WampData
+ */
+pub struct WampDataSerializer;
+
+/// Wraps `value` as `{ variant: value }`, the externally tagged form
+/// serde uses for enum variants carrying data
+fn variant_hash(variant:&str, value:WampData) -> WampData {
+    let mut hash = WampHash::new();
+    hash.insert(variant.to_string(), Box::new(value));
+    WampData::Hash(Box::new(hash), 0)
+}
+
+impl Serializer for WampDataSerializer {
+    type Ok = WampData;
+    type Error = WampError;
+
+    type SerializeSeq = SeqBuilder;
+    type SerializeTuple = SeqBuilder;
+    type SerializeTupleStruct = SeqBuilder;
+    type SerializeTupleVariant = SeqBuilder;
+    type SerializeMap = HashBuilder;
+    type SerializeStruct = HashBuilder;
+    type SerializeStructVariant = HashBuilder;
+
+    fn serialize_bool(self, v:bool) -> Result<WampData, WampError> { Ok(WampData::Bool(v)) }
+    fn serialize_i8(self, v:i8) -> Result<WampData, WampError> { Ok(WampData::Int(v.into())) }
+    fn serialize_i16(self, v:i16) -> Result<WampData, WampError> { Ok(WampData::Int(v.into())) }
+    fn serialize_i32(self, v:i32) -> Result<WampData, WampError> { Ok(WampData::Int(v.into())) }
+    fn serialize_i64(self, v:i64) -> Result<WampData, WampError> { Ok(WampData::Int(v)) }
+    fn serialize_i128(self, v:i128) -> Result<WampData, WampError> { Ok(WampData::BigInt(v)) }
+    fn serialize_u8(self, v:u8) -> Result<WampData, WampError> { Ok(WampData::UInt(v.into())) }
+    fn serialize_u16(self, v:u16) -> Result<WampData, WampError> { Ok(WampData::UInt(v.into())) }
+    fn serialize_u32(self, v:u32) -> Result<WampData, WampError> { Ok(WampData::UInt(v.into())) }
+    fn serialize_u64(self, v:u64) -> Result<WampData, WampError> { Ok(WampData::UInt(v)) }
+    fn serialize_f32(self, v:f32) -> Result<WampData, WampError> { Ok(WampData::Float(v.into())) }
+    fn serialize_f64(self, v:f64) -> Result<WampData, WampError> { Ok(WampData::Float(v)) }
+    fn serialize_char(self, v:char) -> Result<WampData, WampError> { Ok(WampData::Str(v.to_string())) }
+    fn serialize_str(self, v:&str) -> Result<WampData, WampError> { Ok(WampData::Str(v.to_string())) }
+    fn serialize_bytes(self, v:&[u8]) -> Result<WampData, WampError> { Ok(WampData::Bytes(v.to_vec())) }
+    fn serialize_none(self) -> Result<WampData, WampError> { Ok(WampData::None) }
+    fn serialize_unit(self) -> Result<WampData, WampError> { Ok(WampData::None) }
+
+    fn serialize_u128(self, v:u128) -> Result<WampData, WampError> {
+        match u64::try_from(v) {
+            Ok(u) => Ok(WampData::UInt(u)),
+            Err(_) => Err(WampError::SerdeError("u128 doesn't fit in a WAMP integer".to_string())),
+        }
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value:&T) -> Result<WampData, WampError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit_struct(self, _name:&'static str) -> Result<WampData, WampError> {
+        Ok(WampData::None)
+    }
+
+    fn serialize_unit_variant(self, _name:&'static str, _index:u32, variant:&'static str) -> Result<WampData, WampError> {
+        Ok(WampData::Str(variant.to_string()))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name:&'static str, value:&T) -> Result<WampData, WampError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(self, _name:&'static str, _index:u32, variant:&'static str, value:&T) -> Result<WampData, WampError> {
+        Ok(variant_hash(variant, value.serialize(self)?))
+    }
+
+    fn serialize_seq(self, len:Option<usize>) -> Result<SeqBuilder, WampError> {
+        Ok(SeqBuilder { variant: None, items: WampArray::with_capacity(len.unwrap_or(0)) })
+    }
+
+    fn serialize_tuple(self, len:usize) -> Result<SeqBuilder, WampError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name:&'static str, len:usize) -> Result<SeqBuilder, WampError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(self, _name:&'static str, _index:u32, variant:&'static str, len:usize) -> Result<SeqBuilder, WampError> {
+        Ok(SeqBuilder { variant: Some(variant), items: WampArray::with_capacity(len) })
+    }
+
+    fn serialize_map(self, _len:Option<usize>) -> Result<HashBuilder, WampError> {
+        Ok(HashBuilder { variant: None, items: WampHash::new(), key: None })
+    }
+
+    fn serialize_struct(self, _name:&'static str, len:usize) -> Result<HashBuilder, WampError> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(self, _name:&'static str, _index:u32, variant:&'static str, _len:usize) -> Result<HashBuilder, WampError> {
+        Ok(HashBuilder { variant: Some(variant), items: WampHash::new(), key: None })
+    }
+}
+
+pub struct SeqBuilder {
+    variant: Option<&'static str>,
+    items: WampArray,
+}
+
+impl SeqBuilder {
+    fn push<T: Serialize + ?Sized>(&mut self, value:&T) -> Result<(), WampError> {
+        self.items.push(value.serialize(WampDataSerializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<WampData, WampError> {
+        let array = WampData::Array(Box::new(self.items), 0);
+        match self.variant {
+            Some(variant) => Ok(variant_hash(variant, array)),
+            None => Ok(array),
+        }
+    }
+}
+
+impl ser::SerializeSeq for SeqBuilder {
+    type Ok = WampData;
+    type Error = WampError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value:&T) -> Result<(), WampError> { self.push(value) }
+    fn end(self) -> Result<WampData, WampError> { self.finish() }
+}
+
+impl ser::SerializeTuple for SeqBuilder {
+    type Ok = WampData;
+    type Error = WampError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value:&T) -> Result<(), WampError> { self.push(value) }
+    fn end(self) -> Result<WampData, WampError> { self.finish() }
+}
+
+impl ser::SerializeTupleStruct for SeqBuilder {
+    type Ok = WampData;
+    type Error = WampError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value:&T) -> Result<(), WampError> { self.push(value) }
+    fn end(self) -> Result<WampData, WampError> { self.finish() }
+}
+
+impl ser::SerializeTupleVariant for SeqBuilder {
+    type Ok = WampData;
+    type Error = WampError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value:&T) -> Result<(), WampError> { self.push(value) }
+    fn end(self) -> Result<WampData, WampError> { self.finish() }
+}
+
+pub struct HashBuilder {
+    variant: Option<&'static str>,
+    items: WampHash,
+    key: Option<String>,
+}
+
+impl HashBuilder {
+    fn finish(self) -> Result<WampData, WampError> {
+        let hash = WampData::Hash(Box::new(self.items), 0);
+        match self.variant {
+            Some(variant) => Ok(variant_hash(variant, hash)),
+            None => Ok(hash),
+        }
+    }
+}
+
+/// WAMP dicts are keyed by strings, so numbers and chars are written
+/// out as text and anything else is refused
+fn key_string(key:WampData) -> Result<String, WampError> {
+    match key {
+        WampData::Str(s) => Ok(s),
+        WampData::Int(i) => Ok(i.to_string()),
+        WampData::UInt(u) => Ok(u.to_string()),
+        WampData::Bool(b) => Ok(b.to_string()),
+        other => Err(WampError::SerdeError(format!("map key must be a string, got {:?}", other))),
+    }
+}
+
+impl ser::SerializeMap for HashBuilder {
+    type Ok = WampData;
+    type Error = WampError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key:&T) -> Result<(), WampError> {
+        self.key = Some(key_string(key.serialize(WampDataSerializer)?)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value:&T) -> Result<(), WampError> {
+        let key = self.key.take().ok_or_else(|| WampError::SerdeError("map value without a key".to_string()))?;
+        self.items.insert(key, Box::new(value.serialize(WampDataSerializer)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<WampData, WampError> { self.finish() }
+}
+
+impl ser::SerializeStruct for HashBuilder {
+    type Ok = WampData;
+    type Error = WampError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key:&'static str, value:&T) -> Result<(), WampError> {
+        self.items.insert(key.to_string(), Box::new(value.serialize(WampDataSerializer)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<WampData, WampError> { self.finish() }
+}
+
+impl ser::SerializeStructVariant for HashBuilder {
+    type Ok = WampData;
+    type Error = WampError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key:&'static str, value:&T) -> Result<(), WampError> {
+        ser::SerializeStruct::serialize_field(self, key, value)
+    }
+
+    fn end(self) -> Result<WampData, WampError> { self.finish() }
+}
+
+/*
+ * WampData as a serde Deserializer so values can be read straight into
+ * serde types
+ */
+impl<'de> IntoDeserializer<'de, WampError> for WampData {
+    type Deserializer = WampData;
+
+    fn into_deserializer(self) -> WampData {
+        self
+    }
+}
+
+impl<'de> Deserializer<'de> for WampData {
+    type Error = WampError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor:V) -> Result<V::Value, WampError> {
+        match self {
+            WampData::Float(f) => visitor.visit_f64(f),
+            WampData::Int(i) => visitor.visit_i64(i),
+            WampData::UInt(u) => visitor.visit_u64(u),
+            WampData::BigInt(i) => visitor.visit_i128(i),
+            WampData::Bool(b) => visitor.visit_bool(b),
+            WampData::Str(s) => visitor.visit_string(s),
+            WampData::Bytes(b) => visitor.visit_byte_buf(b),
+            WampData::Array(a, _) => {
+                let mut seq = SeqDeserializer::new(a.into_iter());
+                let value = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(value)
+            },
+            WampData::Hash(h, _) => {
+                let mut map = MapDeserializer::new(h.into_iter().map(|(k, v)| (k, *v)));
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(value)
+            },
+            WampData::Tagged(_, v) => v.deserialize_any(visitor),
+            WampData::Simple(s) => visitor.visit_u8(s),
+            ref data @ WampData::Serializable(_) => {
+                WampData::from_slice(data.to_vec())?.deserialize_any(visitor)
+            },
+            WampData::Undefined | WampData::None => visitor.visit_unit(),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor:V) -> Result<V::Value, WampError> {
+        match self {
+            WampData::Undefined | WampData::None => visitor.visit_none(),
+            other => visitor.visit_some(other),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name:&'static str, visitor:V) -> Result<V::Value, WampError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(self, _name:&'static str, _variants:&'static [&'static str], visitor:V) -> Result<V::Value, WampError> {
+        match self {
+            WampData::Str(variant) => visitor.visit_enum(IntoDeserializer::<WampError>::into_deserializer(variant)),
+            WampData::Hash(h, _) if h.len() == 1 => {
+                let (variant, value) = h.into_iter().next().unwrap();
+                visitor.visit_enum(EnumDeserializer { variant, value: *value })
+            },
+            other => Err(de::Error::invalid_type(unexpected(&other), &"a string or single entry dict naming the variant")),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+/// How `data` is described in serde's type errors
+fn unexpected(data:&WampData) -> de::Unexpected<'_> {
+    match data {
+        WampData::Float(f) => de::Unexpected::Float(*f),
+        WampData::Int(i) => de::Unexpected::Signed(*i),
+        WampData::UInt(u) => de::Unexpected::Unsigned(*u),
+        WampData::Bool(b) => de::Unexpected::Bool(*b),
+        WampData::Str(s) => de::Unexpected::Str(s),
+        WampData::Bytes(b) => de::Unexpected::Bytes(b),
+        WampData::Array(_, _) => de::Unexpected::Seq,
+        WampData::Hash(_, _) => de::Unexpected::Map,
+        WampData::Undefined | WampData::None => de::Unexpected::Unit,
+        _ => de::Unexpected::Other("WAMP value"),
+    }
+}
+
+struct EnumDeserializer {
+    variant: String,
+    value: WampData,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumDeserializer {
+    type Error = WampError;
+    type Variant = WampData;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(self, seed:V) -> Result<(V::Value, WampData), WampError> {
+        let variant = seed.deserialize(IntoDeserializer::<WampError>::into_deserializer(self.variant))?;
+        Ok((variant, self.value))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for WampData {
+    type Error = WampError;
+
+    fn unit_variant(self) -> Result<(), WampError> {
+        match self {
+            WampData::Undefined | WampData::None => Ok(()),
+            other => Err(de::Error::invalid_type(unexpected(&other), &"unit variant")),
+        }
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(self, seed:T) -> Result<T::Value, WampError> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len:usize, visitor:V) -> Result<V::Value, WampError> {
+        self.deserialize_any(visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(self, _fields:&'static [&'static str], visitor:V) -> Result<V::Value, WampError> {
+        self.deserialize_any(visitor)
+    }
+}
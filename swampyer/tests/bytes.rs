@@ -0,0 +1,56 @@
+use swampyer::{Serializer, WampArray, WampData};
+
+const DATA:&[u8] = &[0x00, 0x01, 0xfe, 0xff, b'h', b'i'];
+
+fn bytes(data:&WampData) -> Vec<u8> {
+    data.as_bytes().unwrap().to_vec()
+}
+
+#[test]
+fn cbor_round_trip() {
+    let encoded = Serializer::Cbor.encode(&WampData::from(DATA));
+    // Major type 2 with the length, then the raw bytes
+    assert_eq!(encoded[0], 0x46);
+    assert_eq!(&encoded[1..], DATA);
+    assert_eq!(bytes(&Serializer::Cbor.decode(encoded).unwrap()), DATA);
+}
+
+#[test]
+fn cbor_indefinite_bytes() {
+    let data = WampData::from_slice(vec![0x5f, 0x42, 0x01, 0x02, 0x41, 0x03, 0xff]).unwrap();
+    assert_eq!(bytes(&data), [1, 2, 3]);
+}
+
+#[test]
+fn json_round_trip() {
+    let encoded = Serializer::Json.encode(&WampData::from(DATA.to_vec()));
+    assert_eq!(String::from_utf8(encoded.clone()).unwrap(), "\"\\u0000AAH+/2hp\"");
+    assert_eq!(bytes(&Serializer::Json.decode(encoded).unwrap()), DATA);
+}
+
+#[test]
+fn empty_bytes() {
+    let empty = WampData::from(Vec::<u8>::new());
+    for serializer in [Serializer::Cbor, Serializer::Json] {
+        assert!(bytes(&serializer.decode(serializer.encode(&empty)).unwrap()).is_empty());
+    }
+}
+
+#[test]
+fn nested_bytes() {
+    let list:WampArray = vec![WampData::from(DATA), "text".into()];
+    let data = WampData::from(list);
+    for serializer in [Serializer::Cbor, Serializer::Json] {
+        let decoded = serializer.decode(serializer.encode(&data)).unwrap();
+        assert_eq!(bytes(decoded.a(0).unwrap()), DATA);
+        assert_eq!(decoded.a(1).unwrap().to_json(), "text");
+    }
+}
+
+#[test]
+fn json_strings_stay_strings() {
+    // Only a leading NUL marks base64, and invalid base64 is left alone
+    let data = WampData::from_json_slice(br#"["AAH+","\u0000not base64!"]"#).unwrap();
+    assert!(data.a(0).unwrap().as_bytes().is_err());
+    assert_eq!(data.a(1).unwrap().to_json(), "\u{0}not base64!");
+}
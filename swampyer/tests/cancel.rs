@@ -0,0 +1,86 @@
+#![cfg(feature = "smol")]
+
+mod common;
+
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use swampyer::{wdata, CallOptionsBuilder, CancelMode, WampClient, WampError};
+use common::{run, until, Options, Router};
+
+/// Waits for the client to forget about every request it made
+async fn nothing_pending(client:&WampClient) {
+    for _ in 0..500 {
+        if client.requests_pending().await == 0 {
+            return;
+        }
+        smol::Timer::after(Duration::from_millis(10)).await;
+    }
+    panic!("requests still pending");
+}
+
+fn cancels(router:&Router) -> Vec<Value> {
+    router.received(49)
+}
+
+#[test]
+fn timeout_sends_cancel_on_drop() {
+    let router = Router::start();
+    let request_id = run(router.builder(), {
+        let router = router.clone();
+        move |mut client:WampClient| async move {
+            let options = CallOptionsBuilder::default()
+                              .timeout(Duration::from_millis(100))
+                              .cancel_mode(CancelMode::Kill)
+                              .build()
+                              .unwrap();
+            let call = client.start_call("test.hang", wdata!([]), wdata!({}), options).await.unwrap();
+            let request_id = call.request_id();
+            let result = call.result().await;
+            assert!(matches!(result, Err(WampError::Timeout)), "{:?}", result);
+
+            // The router never answers, so only the drop clears the entry
+            until("the CANCEL", || !cancels(&router).is_empty()).await;
+            nothing_pending(&client).await;
+            request_id
+        }
+    });
+    assert_eq!(cancels(&router), [json!([49, request_id, {"mode": "kill"}])]);
+}
+
+#[test]
+fn cancel_with_each_mode() {
+    let router = Router::start();
+    let request_ids = run(router.builder(), |mut client:WampClient| async move {
+        let mut request_ids = Vec::new();
+        for mode in [CancelMode::Skip, CancelMode::Kill, CancelMode::KillNoWait] {
+            let call = client.start_call("test.slow", wdata!([]), wdata!({}), Default::default()).await.unwrap();
+            request_ids.push(call.request_id());
+            call.cancel(mode).await.unwrap();
+            let result = call.result().await;
+            assert!(matches!(result, Err(WampError::Cancelled)), "{:?}", result);
+        }
+        nothing_pending(&client).await;
+        request_ids
+    });
+    assert_eq!(cancels(&router), [
+        json!([49, request_ids[0], {"mode": "skip"}]),
+        json!([49, request_ids[1], {"mode": "kill"}]),
+        json!([49, request_ids[2], {"mode": "killnowait"}]),
+    ]);
+}
+
+#[test]
+fn cancel_needs_call_canceling() {
+    let router = Router::start_with(Options { without: vec![("dealer", "call_canceling")], ..Options::default() });
+    run(router.builder(), |mut client:WampClient| async move {
+        let call = client.start_call("test.hang", wdata!([]), wdata!({}), Default::default()).await.unwrap();
+        let result = call.cancel(CancelMode::Kill).await;
+        assert!(matches!(result, Err(WampError::FeatureNotSupported(ref f)) if f == "call_canceling"), "{:?}", result);
+
+        // Dropping it still forgets the call without sending CANCEL
+        drop(call);
+        nothing_pending(&client).await;
+    });
+    assert!(cancels(&router).is_empty());
+}
@@ -0,0 +1,470 @@
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+#[cfg(feature = "smol")]
+use std::future::Future;
+use std::io::{Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use swampyer::{Serializer, WampClientBuilder};
+#[cfg(feature = "smol")]
+use swampyer::WampClient;
+
+/*
+ * A small in-process router speaking JSON over rawsocket. It does just
+ * enough of the dealer and broker roles for the client to be driven
+ * through real sessions:
+ *
+ *   - subscribing twice to a topic and match policy hands back the same
+ *     subscription id, like real routers do
+ *   - calling `test.drop` with a topic stores a missed event in that
+ *     topic's history and closes the connection
+ *   - `wamp.subscription.get_events` returns the stored history
+ *   - `wamp.session.add_testament` accepts any testament
+ *   - calling `test.goodbye` makes the router close the session with
+ *     GOODBYE
+ *   - `test.progress` with a count sends that many progressive results,
+ *     if asked for, before the final one
+ *   - `test.hang` is never answered, not even when cancelled, while
+ *     `test.slow` is only answered by cancelling it
+ *
+ * Publishers and callers that disclose themselves are identified by
+ * session id with an `anonymous` authid and authrole.
+ *
+ * Every message it receives is kept so tests can check what the client
+ * sent.
+ */
+#[derive(Clone)]
+pub struct Router {
+    pub port: u16,
+    state: Arc<Mutex<State>>,
+}
+
+/*
+ * Ways the router can be told to misbehave
+ */
+#[derive(Default)]
+pub struct Options {
+    /// ABORT every HELLO with this reason
+    pub abort: Option<String>,
+    /// Role and feature pairs left out of WELCOME
+    pub without: Vec<(&'static str, &'static str)>,
+}
+
+type Writer = Arc<Mutex<TcpStream>>;
+
+struct Subscription {
+    id: u64,
+    connection: u64,
+    topic: String,
+    match_policy: String,
+    writer: Writer,
+}
+
+struct Registration {
+    id: u64,
+    connection: u64,
+    procedure: String,
+    writer: Writer,
+}
+
+#[derive(Default)]
+struct State {
+    options: Options,
+    next_id: u64,
+    received: Vec<Value>,
+    // Connection to the session established on it
+    sessions: HashMap<u64, u64>,
+    subscriptions: Vec<Subscription>,
+    registrations: Vec<Registration>,
+    // Invocation id to the caller and its CALL request id
+    invocations: HashMap<u64, (Writer, u64)>,
+    // Connection and request id of a progressive call to the invocation
+    // its chunks go out as
+    chunks: HashMap<(u64, u64), u64>,
+    // Connection and request id of calls to `test.slow`
+    slow: Vec<(u64, u64)>,
+    history: HashMap<String, Vec<Value>>,
+}
+
+impl State {
+    fn id(&mut self) -> u64 {
+        self.next_id += 1;
+        1000 + self.next_id
+    }
+}
+
+fn send(writer:&Writer, message:Value) {
+    let data = message.to_string().into_bytes();
+    let mut frame = (data.len() as u32).to_be_bytes().to_vec();
+    frame.extend(data);
+    let _ = writer.lock().unwrap().write_all(&frame);
+}
+
+fn payload(message:&[Value], i:usize, default:Value) -> Value {
+    message.get(i).cloned().unwrap_or(default)
+}
+
+/// `details` with the `ppt_*` entries of `options` copied over, as the
+/// router passes them on from caller to callee and back
+fn with_ppt(options:&Value, mut details:Value) -> Value {
+    if let Some(options) = options.as_object() {
+        for (key, value) in options.iter().filter(|(key, _)| key.starts_with("ppt_")) {
+            details[key] = value.clone();
+        }
+    }
+    details
+}
+
+/// `details` with the publisher or caller identified, when `options`
+/// ask for it with `disclose_me`
+fn disclosed(state:&State, connection:u64, options:&Value, who:&str, mut details:Value) -> Value {
+    if options.get("disclose_me").and_then(Value::as_bool).unwrap_or(false) {
+        details[who] = json!(state.sessions.get(&connection));
+        details[format!("{}_authid", who)] = json!("anonymous");
+        details[format!("{}_authrole", who)] = json!("anonymous");
+    }
+    details
+}
+
+fn welcome_details(options:&Options) -> Value {
+    let mut details = json!({
+        "roles": {
+            "broker": { "features": {
+                "pattern_based_subscription": true,
+                "publisher_exclusion": true,
+                "publisher_identification": true,
+                "subscriber_blackwhite_listing": true,
+                "event_history": true,
+                "session_meta_api": true,
+                "subscription_meta_api": true,
+                "payload_passthru_mode": true,
+            }},
+            "dealer": { "features": {
+                "progressive_call_results": true,
+                "call_canceling": true,
+                "call_timeout": true,
+                "caller_identification": true,
+                "shared_registration": true,
+                "pattern_based_registration": true,
+                "progressive_call_invocations": true,
+                "session_meta_api": true,
+                "registration_meta_api": true,
+                "subscription_meta_api": true,
+                "testament_meta_api": true,
+                "payload_passthru_mode": true,
+            }},
+        }
+    });
+    for (role, feature) in options.without.iter() {
+        if let Some(features) = details["roles"][role]["features"].as_object_mut() {
+            features.remove(*feature);
+        }
+    }
+    details
+}
+
+impl Router {
+    pub fn start() -> Router {
+        Router::start_with(Options::default())
+    }
+
+    pub fn start_with(options:Options) -> Router {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let state = Arc::new(Mutex::new(State { options, ..State::default() }));
+
+        let shared = state.clone();
+        thread::spawn(move || {
+            for (connection, stream) in listener.incoming().enumerate() {
+                let state = shared.clone();
+                if let Ok(stream) = stream {
+                    thread::spawn(move || serve(state, connection as u64, stream));
+                }
+            }
+        });
+        Router { port, state }
+    }
+
+    /// A client builder pointed at this router
+    pub fn builder(&self) -> WampClientBuilder {
+        WampClientBuilder::default()
+            .url(format!("127.0.0.1:{}", self.port))
+            .realm("realm1")
+            .serializer(Serializer::Json)
+            .call_timeout(Duration::from_secs(5))
+    }
+
+    /// Messages of type `code` received so far, oldest first
+    pub fn received(&self, code:u64) -> Vec<Value> {
+        self.state.lock().unwrap().received.iter()
+            .filter(|message| message[0] == json!(code))
+            .cloned()
+            .collect()
+    }
+}
+
+fn serve(state:Arc<Mutex<State>>, connection:u64, mut stream:TcpStream) {
+    let mut handshake = [0u8; 4];
+    if stream.read_exact(&mut handshake).is_err() {
+        return;
+    }
+    let _ = stream.write_all(&[0x7f, 0xf1, 0, 0]);
+    let writer:Writer = Arc::new(Mutex::new(stream.try_clone().unwrap()));
+
+    loop {
+        let mut header = [0u8; 4];
+        if stream.read_exact(&mut header).is_err() {
+            break;
+        }
+        let length = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+        let mut body = vec![0u8; length];
+        if stream.read_exact(&mut body).is_err() {
+            break;
+        }
+        // Pings and pongs
+        if header[0] & 7 != 0 {
+            continue;
+        }
+        let message:Vec<Value> = match serde_json::from_slice(&body) {
+            Ok(Value::Array(message)) => message,
+            _ => continue,
+        };
+        if !route(&state, connection, &writer, &message) {
+            let _ = stream.shutdown(Shutdown::Both);
+            break;
+        }
+    }
+
+    // Like a real router, forget everything the session set up
+    let mut state = state.lock().unwrap();
+    state.subscriptions.retain(|s| s.connection != connection);
+    state.registrations.retain(|r| r.connection != connection);
+}
+
+/// Handles one message, returning false when the connection should close
+fn route(state:&Arc<Mutex<State>>, connection:u64, writer:&Writer, message:&[Value]) -> bool {
+    let mut state = state.lock().unwrap();
+    state.received.push(Value::Array(message.to_vec()));
+    let code = message[0].as_u64().unwrap_or(0);
+    match code {
+        // HELLO
+        1 => {
+            if let Some(ref reason) = state.options.abort {
+                send(writer, json!([3, {}, reason]));
+                return false;
+            }
+            let session = state.id();
+            state.sessions.insert(connection, session);
+            send(writer, json!([2, session, welcome_details(&state.options)]));
+        },
+        // GOODBYE answering ours
+        6 if message[2] == json!("wamp.close.goodbye_and_out") => return false,
+        // GOODBYE
+        6 => send(writer, json!([6, {}, "wamp.close.goodbye_and_out"])),
+        // PUBLISH
+        16 => {
+            let request = message[1].clone();
+            let options = payload(message, 2, json!({}));
+            let topic = message[3].as_str().unwrap_or_default().to_string();
+            let args = payload(message, 4, json!([]));
+            let kwargs = payload(message, 5, json!({}));
+            let publication = state.id();
+            state.history.entry(topic.clone()).or_default().push(json!({
+                "publication": publication, "topic": topic, "timestamp": "now", "args": args, "kwargs": kwargs,
+            }));
+            let exclude_me = options.get("exclude_me").and_then(Value::as_bool).unwrap_or(true);
+            for subscription in state.subscriptions.iter() {
+                let matches = subscription.topic == topic
+                              || (subscription.match_policy == "prefix" && topic.starts_with(&subscription.topic));
+                if !matches || (exclude_me && subscription.connection == connection) {
+                    continue;
+                }
+                let details = disclosed(&state, connection, &options, "publisher", json!({"topic": topic}));
+                send(&subscription.writer, json!([36, subscription.id, publication, details, args, kwargs]));
+            }
+            if options.get("acknowledge").and_then(Value::as_bool).unwrap_or(false) {
+                send(writer, json!([17, request, publication]));
+            }
+        },
+        // SUBSCRIBE
+        32 => {
+            let options = payload(message, 2, json!({}));
+            let topic = message[3].as_str().unwrap_or_default().to_string();
+            let match_policy = options.get("match").and_then(Value::as_str).unwrap_or("exact").to_string();
+            let existing = state.subscriptions.iter()
+                                .find(|s| s.connection == connection && s.topic == topic && s.match_policy == match_policy)
+                                .map(|s| s.id);
+            let id = match existing {
+                Some(id) => id,
+                None => {
+                    let id = state.id();
+                    state.subscriptions.push(Subscription { id, connection, topic, match_policy, writer: writer.clone() });
+                    id
+                },
+            };
+            send(writer, json!([33, message[1], id]));
+        },
+        // CALL
+        48 => {
+            let request = message[1].as_u64().unwrap_or(0);
+            let procedure = message[3].as_str().unwrap_or_default().to_string();
+            let args = payload(message, 4, json!([]));
+            let kwargs = payload(message, 5, json!({}));
+            match procedure.as_str() {
+                "test.drop" => {
+                    let topic = args[0].as_str().unwrap_or_default().to_string();
+                    let publication = state.id();
+                    state.history.entry(topic.clone()).or_default().push(json!({
+                        "publication": publication, "topic": topic, "timestamp": "now", "args": ["missed"], "kwargs": {},
+                    }));
+                    return false;
+                },
+                "test.goodbye" => send(writer, json!([6, {}, "wamp.close.system_shutdown"])),
+                "test.progress" => {
+                    let receive_progress = message[2].get("receive_progress").and_then(Value::as_bool).unwrap_or(false);
+                    if receive_progress {
+                        for i in 0..args[0].as_u64().unwrap_or(0) {
+                            send(writer, json!([50, request, {"progress": true}, [i]]));
+                        }
+                    }
+                    send(writer, json!([50, request, {}, ["done"]]));
+                },
+                "test.hang" => (),
+                "wamp.session.add_testament" => send(writer, json!([50, request, {}])),
+                "test.slow" => state.slow.push((connection, request)),
+                "wamp.subscription.get_events" => {
+                    let id = args[0].as_u64().unwrap_or(0);
+                    let limit = args[1].as_u64().unwrap_or(0) as usize;
+                    let topic = state.subscriptions.iter().find(|s| s.id == id).map(|s| s.topic.clone()).unwrap_or_default();
+                    let events = state.history.get(&topic).cloned().unwrap_or_default();
+                    let start = events.len().saturating_sub(limit);
+                    send(writer, json!([50, request, {}, [events[start..].to_vec()]]));
+                },
+                _ => {
+                    let callee = state.registrations.iter()
+                                      .find(|r| r.procedure == procedure)
+                                      .map(|r| (r.id, r.writer.clone()));
+                    match callee {
+                        Some((registration, callee)) => {
+                            let progress = message[2].get("progress").and_then(Value::as_bool).unwrap_or(false);
+                            let invocation = match state.chunks.get(&(connection, request)) {
+                                Some(invocation) => *invocation,
+                                None => state.id(),
+                            };
+                            if progress {
+                                state.chunks.insert((connection, request), invocation);
+                            }
+                            else {
+                                state.chunks.remove(&(connection, request));
+                            }
+                            state.invocations.insert(invocation, (writer.clone(), request));
+                            let details = disclosed(&state, connection, &message[2], "caller", json!({"procedure": procedure}));
+                            let mut details = with_ppt(&message[2], details);
+                            if progress {
+                                details["progress"] = json!(true);
+                            }
+                            send(&callee, json!([68, invocation, registration, details, args, kwargs]));
+                        },
+                        None => send(writer, json!([8, 48, request, {}, "wamp.error.no_such_procedure"])),
+                    }
+                },
+            }
+        },
+        // CANCEL
+        49 => {
+            let request = message[1].as_u64().unwrap_or(0);
+            if let Some(i) = state.slow.iter().position(|slow| *slow == (connection, request)) {
+                state.slow.remove(i);
+                send(writer, json!([8, 48, request, {}, "wamp.error.canceled"]));
+            }
+        },
+        // REGISTER
+        64 => {
+            let procedure = message[3].as_str().unwrap_or_default().to_string();
+            if state.registrations.iter().any(|r| r.procedure == procedure) {
+                send(writer, json!([8, 64, message[1], {}, "wamp.error.procedure_already_exists"]));
+            }
+            else {
+                let id = state.id();
+                state.registrations.push(Registration { id, connection, procedure, writer: writer.clone() });
+                send(writer, json!([65, message[1], id]));
+            }
+        },
+        // YIELD
+        70 => {
+            let invocation = message[1].as_u64().unwrap_or(0);
+            if let Some((caller, request)) = state.invocations.remove(&invocation) {
+                let details = with_ppt(&message[2], json!({}));
+                send(&caller, json!([50, request, details, payload(message, 3, json!([])), payload(message, 4, json!({}))]));
+            }
+        },
+        // ERROR answering an INVOCATION
+        8 if message[1] == json!(68) => {
+            let invocation = message[2].as_u64().unwrap_or(0);
+            if let Some((caller, request)) = state.invocations.remove(&invocation) {
+                send(&caller, json!([8, 48, request, {}, message[4], payload(message, 5, json!([])), payload(message, 6, json!({}))]));
+            }
+        },
+        _ => (),
+    }
+    true
+}
+
+/// Connects a client built from `builder` and runs `body` once the
+/// session is established, returning what it returns. Panics if the
+/// body panics, the connection ends or it all takes too long.
+#[cfg(feature = "smol")]
+pub fn run<T, F, Fut>(builder:WampClientBuilder, body:F) -> T
+where
+    T: Send + 'static,
+    F: FnOnce(WampClient) -> Fut + Send + 'static,
+    Fut: Future<Output = T> + Send + 'static,
+{
+    let mut client = builder.build().unwrap();
+    let (sender, receiver) = async_channel::bounded(1);
+    let body = Mutex::new(Some(body));
+
+    smol::block_on(async move {
+        client.onjoin(move |client, _| {
+            // Later sessions after a reconnect don't run it again
+            let body = body.lock().unwrap().take();
+            let sender = sender.clone();
+            async move {
+                if let Some(body) = body {
+                    let _ = sender.send(body(client).await).await;
+                }
+            }
+        }).await;
+
+        let finished = async {
+            receiver.recv().await.expect("test body panicked")
+        };
+        let running = async {
+            let result = client.run().await;
+            panic!("connection ended: {:?}", result)
+        };
+        let timeout = async {
+            smol::Timer::after(Duration::from_secs(20)).await;
+            panic!("timed out")
+        };
+        smol::future::or(finished, smol::future::or(running, timeout)).await
+    })
+}
+
+/// Polls `done` until it holds, panicking if that takes more than a
+/// few seconds
+#[cfg(feature = "smol")]
+pub async fn until<F:FnMut() -> bool>(what:&str, mut done:F) {
+    for _ in 0..500 {
+        if done() {
+            return;
+        }
+        smol::Timer::after(Duration::from_millis(10)).await;
+    }
+    panic!("timed out waiting for {}", what);
+}
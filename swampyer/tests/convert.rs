@@ -0,0 +1,52 @@
+use swampyer::{Bytes, FromWampData, Serializer, ToWampData, WampData, WampError};
+
+fn out_of_range<T: FromWampData + std::fmt::Debug>(data:WampData) -> String {
+    match T::from_wamp_data(&data) {
+        Err(WampError::ConversionError(reason)) => reason,
+        other => panic!("expected a conversion error, got {:?}", other),
+    }
+}
+
+#[test]
+fn big_integers() {
+    // CBOR integers outside i64 and u64 decode as BigInt
+    assert_eq!(u64::from_wamp_data(&WampData::BigInt(u64::MAX as i128)).unwrap(), u64::MAX);
+    assert_eq!(i64::from_wamp_data(&WampData::BigInt(i64::MIN as i128)).unwrap(), i64::MIN);
+    assert_eq!(u8::from_wamp_data(&WampData::BigInt(7)).unwrap(), 7);
+    assert_eq!(i32::from_wamp_data(&WampData::BigInt(-7)).unwrap(), -7);
+    assert_eq!(f64::from_wamp_data(&WampData::BigInt(-(1 << 70))).unwrap(), -(2f64.powi(70)));
+
+    assert!(out_of_range::<u64>(WampData::BigInt(-1)).contains("out of range for u64"));
+    assert!(out_of_range::<i64>(WampData::BigInt(i64::MAX as i128 + 1)).contains("out of range for i64"));
+    assert!(out_of_range::<u8>(WampData::BigInt(256)).contains("out of range for u8"));
+}
+
+#[test]
+fn integers_from_cbor() {
+    // -2^64, the smallest CBOR integer
+    let data = WampData::from_slice(vec![0x3b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]).unwrap();
+    assert!(out_of_range::<i64>(*data).contains("out of range"));
+    let data = WampData::from_slice(vec![0x18, 0xff]).unwrap();
+    assert_eq!(u8::from_wamp_data(&data).unwrap(), 255);
+    assert!(out_of_range::<i8>(*data).contains("out of range for i8"));
+}
+
+#[test]
+fn bytes() {
+    let bytes = Bytes(vec![0, 1, 255]);
+    let data = bytes.to_wamp_data();
+    assert_eq!(data.as_bytes().unwrap(), [0, 1, 255]);
+    assert_eq!(Bytes::from_wamp_data(&data).unwrap(), bytes);
+    assert_eq!(Vec::from(bytes.clone()), vec![0, 1, 255]);
+
+    // Through both serializers and back
+    for serializer in [Serializer::Cbor, Serializer::Json] {
+        let decoded = serializer.decode(serializer.encode(&data)).unwrap();
+        assert_eq!(Bytes::from_wamp_data(&decoded).unwrap(), bytes);
+    }
+
+    // A plain Vec<u8> stays a list of integers
+    assert!(vec![1u8, 2].to_wamp_data().as_bytes().is_err());
+    assert!(Bytes::from_wamp_data(&vec![1u8, 2].to_wamp_data()).is_err());
+    assert!(Option::<Bytes>::from_wamp_data(&WampData::None).unwrap().is_none());
+}
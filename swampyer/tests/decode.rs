@@ -0,0 +1,128 @@
+use swampyer::{DecodeError, Serializer, WampData, WampError};
+
+fn cbor(data:&[u8]) -> Result<Box<WampData>, WampError> {
+    WampData::from_slice(data.to_vec())
+}
+
+fn decode_error(result:Result<Box<WampData>, WampError>) -> DecodeError {
+    match result {
+        Err(WampError::DecodeError(e)) => e,
+        other => panic!("expected a decode error, got {:?}", other),
+    }
+}
+
+#[test]
+fn valid_cbor() {
+    // [1, "a", {"k": -1}]
+    let data = cbor(&[0x83, 0x01, 0x61, b'a', 0xa1, 0x61, b'k', 0x20]).unwrap();
+    assert_eq!(data.to_json().to_string(), "[1,\"a\",{\"k\":-1}]");
+    // Indefinite length array and map
+    let data = cbor(&[0x9f, 0x01, 0xbf, 0x61, b'k', 0xf5, 0xff, 0xff]).unwrap();
+    assert_eq!(data.to_json().to_string(), "[1,{\"k\":true}]");
+}
+
+#[test]
+fn truncated_input() {
+    assert_eq!(decode_error(cbor(&[])).position, 0);
+    // An array of three holding only one
+    let e = decode_error(cbor(&[0x83, 0x01]));
+    assert_eq!(e.position, 2);
+    assert_eq!(e.expected, "a value");
+    // A string cut short
+    let e = decode_error(cbor(&[0x82, 0x01, 0x65, b'a', b'b']));
+    assert_eq!(e.position, 2);
+    assert_eq!(e.expected, "string");
+    // An indefinite array without its break
+    assert_eq!(decode_error(cbor(&[0x9f, 0x01])).expected, "a value or break");
+}
+
+#[test]
+fn trailing_bytes() {
+    let e = decode_error(cbor(&[0x01, 0x02]));
+    assert_eq!(e.position, 1);
+    assert_eq!(e.expected, "end of data");
+    assert_eq!(e.to_string(), "expected end of data at byte 1: trailing bytes after the value");
+}
+
+#[test]
+fn non_string_map_keys() {
+    // {1: 2}
+    let e = decode_error(cbor(&[0xa1, 0x01, 0x02]));
+    assert_eq!(e.position, 1);
+    assert_eq!(e.expected, "string map key");
+    // {[]: 1}, nested inside an array
+    let e = decode_error(cbor(&[0x81, 0xa1, 0x80, 0x01]));
+    assert_eq!(e.position, 2);
+    assert_eq!(e.expected, "string map key");
+}
+
+#[test]
+fn unexpected_break() {
+    let e = decode_error(cbor(&[0x81, 0xff]));
+    assert_eq!(e.position, 1);
+    assert_eq!(e.expected, "a value");
+}
+
+#[test]
+fn nesting_limit() {
+    // 128 levels of arrays around a value still decode
+    let mut data = vec![0x81; 128];
+    data.push(0x01);
+    assert!(cbor(&data).is_ok());
+
+    // One more is refused at the innermost value
+    let mut data = vec![0x81; 129];
+    data.push(0x01);
+    let e = decode_error(cbor(&data));
+    assert_eq!(e.position, 129);
+    assert_eq!(e.reason, "nested too deeply");
+
+    // Tags and maps count towards it too
+    let mut data = Vec::new();
+    for _ in 0..129 {
+        data.extend([0xa1, 0x61, b'k']);
+    }
+    data.push(0x01);
+    assert_eq!(decode_error(cbor(&data)).reason, "nested too deeply");
+    let mut data = vec![0xc6; 129];
+    data.push(0x01);
+    assert_eq!(decode_error(cbor(&data)).reason, "nested too deeply");
+}
+
+#[test]
+fn invalid_json() {
+    let e = decode_error(Serializer::Json.decode(b"[1, 2".to_vec()));
+    assert_eq!(e.expected, "JSON value");
+    let e = decode_error(Serializer::Json.decode(b"[1, x]".to_vec()));
+    assert_eq!(e.position, 4);
+}
+
+#[test]
+fn half_floats() {
+    assert_eq!(cbor(&[0xf9, 0x3c, 0x00]).unwrap().to_json(), 1.0);
+    assert_eq!(cbor(&[0xf9, 0xc0, 0x00]).unwrap().to_json(), -2.0);
+    assert_eq!(cbor(&[0xf9, 0x35, 0x00]).unwrap().to_json(), 0.3125);
+    // [1.0, 0.5]
+    let data = cbor(&[0x82, 0xf9, 0x3c, 0x00, 0xf9, 0x38, 0x00]).unwrap();
+    assert_eq!(data.to_json().to_string(), "[1.0,0.5]");
+}
+
+#[test]
+fn big_integers() {
+    // JSON keeps anything that fits 64 bits exact
+    assert_eq!(WampData::BigInt(u64::MAX as i128).to_json().to_string(), "18446744073709551615");
+    assert_eq!(WampData::BigInt(i64::MIN as i128).to_json().to_string(), "-9223372036854775808");
+
+    // CBOR goes to bignums past 64 bits
+    assert_eq!(WampData::BigInt(1 << 70).to_vec(), [0xc2, 0x49, 0x40, 0, 0, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(WampData::BigInt(-(1 << 70)).to_vec(), [0xc3, 0x49, 0x3f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+    for i in [1 << 70, -(1 << 70), i128::MAX, i128::MIN, -(u64::MAX as i128) - 1] {
+        let data = cbor(&WampData::BigInt(i).to_vec()).unwrap();
+        assert!(matches!(*data, WampData::BigInt(n) if n == i), "{} came back as {:?}", i, data);
+    }
+
+    // Bignums too big for a BigInt stay tagged
+    let mut data = vec![0xc2, 0x51];
+    data.extend([0xff; 17]);
+    assert!(matches!(*cbor(&data).unwrap(), WampData::Tagged(2, _)));
+}
@@ -0,0 +1,178 @@
+use swampyer::{FromWampData, ToWampData, Wamp, WampData, WampError};
+
+fn json<T: ToWampData>(value:&T) -> String {
+    value.to_wamp_data().to_json().to_string()
+}
+
+fn parse<T: FromWampData>(text:&str) -> Result<T, WampError> {
+    T::from_wamp_data(&WampData::from_json_slice(text.as_bytes()).unwrap())
+}
+
+fn conversion_error<T: FromWampData + std::fmt::Debug>(text:&str) -> String {
+    match parse::<T>(text) {
+        Err(WampError::ConversionError(reason)) => reason,
+        other => panic!("expected a conversion error, got {:?}", other),
+    }
+}
+
+#[derive(Debug, PartialEq, Wamp)]
+#[wamp(into)]
+struct Point {
+    x: i64,
+    y: i64,
+}
+
+#[derive(Debug, PartialEq, Wamp)]
+#[wamp(into)]
+#[wamp(args)]
+struct Move {
+    to: Point,
+    speed: f64,
+    #[wamp(optional)]
+    label: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Wamp)]
+#[wamp(into)]
+struct Settings {
+    #[wamp(rename = "display-name")]
+    name: String,
+    #[wamp(skip)]
+    cache: Vec<u64>,
+    #[wamp(optional)]
+    color: Option<String>,
+    #[wamp(default)]
+    retries: u32,
+}
+
+#[derive(Debug, PartialEq, Wamp)]
+#[wamp(into)]
+struct Pair(u8, String);
+
+#[derive(Debug, PartialEq, Wamp)]
+#[wamp(into)]
+struct Marker;
+
+#[derive(Debug, PartialEq, Wamp)]
+#[wamp(into)]
+struct Wrapper<T> {
+    value: T,
+}
+
+#[derive(Debug, PartialEq, Wamp)]
+#[wamp(into)]
+enum Shape {
+    Empty,
+    #[wamp(rename = "dot")]
+    Point(Point),
+    Line(Point, Point),
+    Circle { center: Point, radius: f64 },
+    #[wamp(args)]
+    Rect { width: u32, height: u32 },
+}
+
+#[test]
+fn kwargs_layout() {
+    let point = Point { x: 1, y: -2 };
+    assert_eq!(json(&point), "{\"x\":1,\"y\":-2}");
+    assert_eq!(parse::<Point>("{\"x\":1,\"y\":-2}").unwrap(), point);
+}
+
+#[test]
+fn args_layout() {
+    let step = Move { to: Point { x: 0, y: 3 }, speed: 1.5, label: None };
+    // Unset optional args at the end are left out
+    assert_eq!(json(&step), "[{\"x\":0,\"y\":3},1.5]");
+    assert_eq!(parse::<Move>("[{\"x\":0,\"y\":3},1.5]").unwrap(), step);
+
+    let step = Move { label: Some("run".to_string()), ..step };
+    assert_eq!(json(&step), "[{\"x\":0,\"y\":3},1.5,\"run\"]");
+    assert_eq!(parse::<Move>("[{\"x\":0,\"y\":3},1.5,\"run\"]").unwrap(), step);
+}
+
+#[test]
+fn field_attributes() {
+    let settings = Settings { name: "n".to_string(), cache: vec![1, 2], color: None, retries: 3 };
+    // Skipped fields aren't sent and unset optional ones are left out
+    assert_eq!(json(&settings), "{\"display-name\":\"n\",\"retries\":3}");
+
+    // Skipped, optional and default fields may be missing
+    let parsed = parse::<Settings>("{\"display-name\":\"n\"}").unwrap();
+    assert_eq!(parsed, Settings { name: "n".to_string(), cache: vec![], color: None, retries: 0 });
+    let parsed = parse::<Settings>("{\"display-name\":\"n\",\"color\":\"red\",\"cache\":[9]}").unwrap();
+    assert_eq!(parsed.color.as_deref(), Some("red"));
+    assert!(parsed.cache.is_empty());
+}
+
+#[test]
+fn tuple_and_unit_structs() {
+    assert_eq!(json(&Pair(1, "a".to_string())), "[1,\"a\"]");
+    assert_eq!(parse::<Pair>("[1,\"a\"]").unwrap(), Pair(1, "a".to_string()));
+    assert_eq!(json(&Marker), "null");
+    assert_eq!(parse::<Marker>("null").unwrap(), Marker);
+}
+
+#[test]
+fn generics() {
+    let wrapped = Wrapper { value: vec![Some(1u8), None] };
+    assert_eq!(json(&wrapped), "{\"value\":[1,null]}");
+    assert_eq!(parse::<Wrapper<Vec<Option<u8>>>>("{\"value\":[1,null]}").unwrap(), wrapped);
+}
+
+#[test]
+fn enums() {
+    let cases = [
+        (Shape::Empty, "\"Empty\""),
+        (Shape::Point(Point { x: 1, y: 2 }), "{\"dot\":{\"x\":1,\"y\":2}}"),
+        (Shape::Line(Point { x: 0, y: 0 }, Point { x: 1, y: 1 }), "{\"Line\":[{\"x\":0,\"y\":0},{\"x\":1,\"y\":1}]}"),
+        (Shape::Circle { center: Point { x: 0, y: 0 }, radius: 2.5 }, "{\"Circle\":{\"center\":{\"x\":0,\"y\":0},\"radius\":2.5}}"),
+        (Shape::Rect { width: 3, height: 4 }, "{\"Rect\":[3,4]}"),
+    ];
+    for (shape, expected) in cases {
+        assert_eq!(json(&shape), expected);
+        assert_eq!(parse::<Shape>(expected).unwrap(), shape);
+    }
+}
+
+// Without #[wamp(into)] values are wrapped and written by minicbor
+#[derive(Debug, PartialEq, Wamp, minicbor_derive::Encode)]
+struct Sample {
+    #[n(0)] sensor: u32,
+    #[n(1)] value: u32,
+}
+
+#[test]
+fn into_wamp_data() {
+    let data = WampData::from(Point { x: 1, y: 2 });
+    assert!(matches!(data, WampData::Hash(..)));
+    assert_eq!(Point::try_from(&data).unwrap(), Point { x: 1, y: 2 });
+    // Also works when sent as a custom serializable type
+    let data = WampData::Serializable(std::sync::Arc::new(Point { x: 3, y: 4 }));
+    assert_eq!(Point::from_wamp_data(&data).unwrap(), Point { x: 3, y: 4 });
+}
+
+#[test]
+fn into_serializable() {
+    let data = WampData::from(Sample { sensor: 3, value: 7 });
+    assert!(matches!(data, WampData::Serializable(_)));
+    assert_eq!(data.to_vec(), [0x82, 0x03, 0x07]);
+}
+
+#[test]
+fn conversion_errors() {
+    assert_eq!(conversion_error::<Point>("{\"x\":1}"), "Point: missing field y");
+    assert!(conversion_error::<Point>("{\"x\":1,\"y\":\"two\"}").starts_with("Point.y: expected i64"));
+    assert!(conversion_error::<Point>("[1,2]").starts_with("Point: expected dict"));
+    assert!(conversion_error::<Move>("{}").starts_with("Move: expected array"));
+    assert_eq!(conversion_error::<Move>("[{\"x\":0,\"y\":0}]"), "Move: missing field speed");
+    assert_eq!(conversion_error::<Shape>("\"Hexagon\""), "Shape: unknown variant Hexagon");
+    assert_eq!(conversion_error::<Shape>("\"dot\""), "Shape::dot needs a value");
+    assert!(conversion_error::<Shape>("{\"Rect\":[3]}").contains("Shape::Rect: missing field height"));
+    assert!(conversion_error::<Shape>("1").starts_with("Shape: expected a variant"));
+}
+
+#[test]
+fn compile_errors() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/derive/*.rs");
+}
@@ -0,0 +1,44 @@
+#![cfg(feature = "smol")]
+
+mod common;
+
+use swampyer::{
+    wdata, CallOptionsBuilder, InvokePolicy, MatchPolicy, RegisterOptionsBuilder, SubscribeOptionsBuilder,
+    WampClient, WampError, Yield,
+};
+use common::{run, Options, Router};
+
+fn unsupported<T: std::fmt::Debug>(result:Result<T, WampError>, feature:&str) {
+    assert!(matches!(result, Err(WampError::FeatureNotSupported(ref f)) if f == feature), "{:?}", result);
+}
+
+#[test]
+fn missing_features_are_refused_before_sending() {
+    let router = Router::start_with(Options {
+        without: vec![
+            ("dealer", "progressive_call_results"),
+            ("dealer", "shared_registration"),
+            ("broker", "pattern_based_subscription"),
+        ],
+        ..Options::default()
+    });
+    run(router.builder(), |mut client:WampClient| async move {
+        let options = CallOptionsBuilder::default().receive_progress(true).build().unwrap();
+        unsupported(client.call_with("test.progress", wdata!([2]), wdata!({}), options).await, "progressive_call_results");
+
+        let options = SubscribeOptionsBuilder::default().match_policy(MatchPolicy::Prefix).build().unwrap();
+        unsupported(client.subscribe_with("test.", options, |_, _| async {}).await, "pattern_based_subscription");
+
+        let options = RegisterOptionsBuilder::default().invoke(InvokePolicy::Random).build().unwrap();
+        unsupported(client.register_with("test.shared", options, |_, _| async { Ok(Yield::default()) }).await, "shared_registration");
+
+        // The same requests without the options get through, and are
+        // answered only after anything sent before them arrived
+        client.call("test.progress", wdata!([2]), wdata!({})).await.unwrap();
+        client.subscribe("test.topic", |_, _| async {}).await.unwrap();
+        client.register("test.single", |_, _| async { Ok(Yield::default()) }).await.unwrap();
+    });
+    assert_eq!(router.received(48).len(), 1);
+    assert_eq!(router.received(32).len(), 1);
+    assert_eq!(router.received(64).len(), 1);
+}
@@ -0,0 +1,54 @@
+#![cfg(feature = "smol")]
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use swampyer::{wdata, PublishOptionsBuilder, ReconnectPolicy, SubscribeOptionsBuilder, WampClient};
+use common::{run, until, Router};
+
+/// Makes the router store a missed event on `topic` and drop us
+async fn drop_connection(client:&mut WampClient, topic:&str) {
+    // Fails once the connection goes
+    let _ = client.call("test.drop", wdata!([topic]), wdata!({})).await;
+}
+
+#[test]
+fn replayed_events_are_not_replayed_again() {
+    let router = Router::start();
+    let builder = router.builder()
+                        .reconnect(ReconnectPolicy::exponential(Duration::from_millis(50), Duration::from_millis(100)));
+    let seen = run(builder, {
+        let router = router.clone();
+        move |mut client:WampClient| async move {
+            let seen = Arc::new(Mutex::new(Vec::new()));
+            let options = SubscribeOptionsBuilder::default().history(10u64).build().unwrap();
+            {
+                let seen = seen.clone();
+                client.subscribe_with("test.history", options, move |_, event| {
+                    seen.lock().unwrap().push(format!("{:?}", event.args));
+                    async {}
+                }).await.unwrap();
+            }
+
+            // The first reconnect replays what we missed
+            drop_connection(&mut client, "test.history").await;
+            until("the missed event", || seen.lock().unwrap().len() == 1).await;
+
+            // The second has nothing new to replay. A live event sent
+            // after its history lookup marks the point it's done by.
+            drop_connection(&mut client, "test.elsewhere").await;
+            until("the second history lookup", || {
+                router.received(48).iter().filter(|m| m[3] == "wamp.subscription.get_events").count() == 2
+            }).await;
+            let options = PublishOptionsBuilder::default().exclude_me(false).acknowledge(true).build().unwrap();
+            client.publish_with("test.history", wdata!(["live"]), wdata!({}), options).await.unwrap();
+            until("the live event", || seen.lock().unwrap().len() >= 2).await;
+
+            let seen = seen.lock().unwrap().clone();
+            seen
+        }
+    });
+    assert_eq!(seen, vec!["[Str(\"missed\")]", "[Str(\"live\")]"]);
+}
@@ -0,0 +1,120 @@
+#![cfg(feature = "smol")]
+
+mod common;
+
+use std::sync::Mutex;
+
+use swampyer::{interface, wdata, RpcError, WampClient, WampError};
+use common::{run, Router};
+
+#[interface(prefix = "test.thermostat")]
+trait Thermostat {
+    async fn set_target(&self, celsius:f64) -> Result<(), RpcError>;
+    async fn target(&self) -> f64;
+    async fn schedule(&self, hour:u8, celsius:f64, label:Option<String>) -> String;
+    #[wamp(rename = "reset_all")]
+    async fn reset(&self);
+}
+
+struct Room {
+    target: Mutex<f64>,
+}
+
+impl Thermostat for Room {
+    async fn set_target(&self, celsius:f64) -> Result<(), RpcError> {
+        if celsius > 30.0 {
+            let mut error = RpcError::new("test.error.too_hot");
+            error.args.push(celsius.into());
+            return Err(error);
+        }
+        *self.target.lock().unwrap() = celsius;
+        Ok(())
+    }
+
+    async fn target(&self) -> f64 {
+        *self.target.lock().unwrap()
+    }
+
+    async fn schedule(&self, hour:u8, celsius:f64, label:Option<String>) -> String {
+        format!("{}:00 {} {}", hour, celsius, label.unwrap_or_default())
+    }
+
+    async fn reset(&self) {
+        *self.target.lock().unwrap() = 20.0;
+    }
+}
+
+fn room() -> Room {
+    Room { target: Mutex::new(20.0) }
+}
+
+#[test]
+fn client_calls_the_server() {
+    let router = Router::start();
+    let results = run(router.builder(), |mut client:WampClient| async move {
+        let registrations = ThermostatServer::new(room()).register(&mut client).await.unwrap();
+        assert_eq!(registrations.len(), 4);
+
+        let thermostat = ThermostatClient::new(client.clone());
+        thermostat.set_target(22.5).await.unwrap();
+        let target = thermostat.target().await.unwrap();
+        let scheduled = thermostat.schedule(7, 19.0, Some("morning".to_string())).await.unwrap();
+        let unlabelled = thermostat.schedule(8, 18.0, None).await.unwrap();
+        thermostat.reset().await.unwrap();
+        let reset = thermostat.target().await.unwrap();
+        (target, scheduled, unlabelled, reset)
+    });
+    assert_eq!(results, (22.5, "7:00 19 morning".to_string(), "8:00 18 ".to_string(), 20.0));
+}
+
+#[test]
+fn methods_are_registered_under_the_prefix() {
+    let router = Router::start();
+    let results = run(router.builder(), |mut client:WampClient| async move {
+        ThermostatServer::new(room()).register(&mut client).await.unwrap();
+        let target = client.call("test.thermostat.target", wdata!([]), wdata!({})).await.unwrap();
+        let reset = client.call("test.thermostat.reset_all", wdata!([]), wdata!({})).await.unwrap();
+        let missing = client.call("test.thermostat.reset", wdata!([]), wdata!({})).await;
+        (format!("{:?}", target.args), format!("{:?}", reset.args), missing.is_err())
+    });
+    assert_eq!(results, ("[Float(20.0)]".to_string(), "[]".to_string(), true));
+}
+
+#[test]
+fn errors_reach_the_caller() {
+    let router = Router::start();
+    let (too_hot, invalid) = run(router.builder(), |mut client:WampClient| async move {
+        ThermostatServer::new(room()).register(&mut client).await.unwrap();
+        let thermostat = ThermostatClient::new(client.clone());
+        let too_hot = thermostat.set_target(35.0).await;
+        let invalid = client.call("test.thermostat.set_target", wdata!(["warm"]), wdata!({})).await;
+        (too_hot, invalid)
+    });
+    match too_hot {
+        Err(WampError::CallFailed(e)) => {
+            assert_eq!(e.error, "test.error.too_hot");
+            assert_eq!(format!("{:?}", e.args), "[Float(35.0)]");
+        },
+        other => panic!("expected too_hot, got {:?}", other),
+    }
+    match invalid {
+        Err(WampError::CallFailed(e)) => assert_eq!(e.error, "wamp.error.invalid_argument"),
+        other => panic!("expected invalid_argument, got {:?}", other),
+    }
+}
+
+#[test]
+fn implementations_are_plain_traits() {
+    let room = room();
+    let target = smol::block_on(async {
+        room.set_target(25.0).await.unwrap();
+        room.target().await
+    });
+    assert_eq!(target, 25.0);
+}
+
+#[test]
+fn compile_errors() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/interface/*.rs");
+}
@@ -0,0 +1,109 @@
+use swampyer::{WampData, WampError, WampHash, WampMessage};
+
+fn json(data:WampData) -> String {
+    data.to_json().to_string()
+}
+
+/// Decodes a message the way it arrives over JSON
+fn parse(text:&str) -> Result<WampMessage, WampError> {
+    WampMessage::from_data(&WampData::from_json_slice(text.as_bytes()).unwrap())
+}
+
+/// Parses a message and writes it back out
+fn round_trip(text:&str) -> String {
+    json(parse(text).unwrap().into_data())
+}
+
+/// Asserts a message survives parsing and writing back unchanged
+fn unchanged(text:&str) {
+    assert_eq!(round_trip(text), text);
+}
+
+fn malformed(text:&str) -> String {
+    match parse(text) {
+        Err(WampError::MalformedMessage(reason)) => reason,
+        other => panic!("expected a malformed message, got {:?}", other),
+    }
+}
+
+#[test]
+fn session_messages() {
+    unchanged(r#"[1,"realm1",{"roles":{}}]"#);
+    unchanged(r#"[2,42,{}]"#);
+    unchanged(r#"[3,{},"wamp.error.no_such_realm"]"#);
+    unchanged(r#"[4,"ticket",{}]"#);
+    unchanged(r#"[5,"secret",{}]"#);
+    unchanged(r#"[6,{},"wamp.close.goodbye_and_out"]"#);
+}
+
+#[test]
+fn pubsub_messages() {
+    unchanged(r#"[16,1,{},"a.topic",[1],{"k":"v"}]"#);
+    unchanged(r#"[17,1,5]"#);
+    unchanged(r#"[32,2,{"match":"prefix"},"a"]"#);
+    unchanged(r#"[33,2,7]"#);
+    unchanged(r#"[34,3,7]"#);
+    unchanged(r#"[35,3]"#);
+    unchanged(r#"[36,7,5,{},["x"]]"#);
+}
+
+#[test]
+fn rpc_messages() {
+    unchanged(r#"[48,1,{},"a.proc",[1,2]]"#);
+    unchanged(r#"[49,1,{"mode":"kill"}]"#);
+    unchanged(r#"[50,1,{},[3]]"#);
+    unchanged(r#"[64,2,{},"a.proc"]"#);
+    unchanged(r#"[65,2,9]"#);
+    unchanged(r#"[66,3,9]"#);
+    unchanged(r#"[67,3]"#);
+    unchanged(r#"[68,4,9,{},[1],{"a":1}]"#);
+    unchanged(r#"[69,4,{}]"#);
+    unchanged(r#"[70,4,{},[2]]"#);
+    unchanged(r#"[8,48,1,{},"wamp.error.runtime_error",["oops"]]"#);
+}
+
+#[test]
+fn payload_is_trimmed() {
+    // Missing args and kwargs read as empty and empty ones are left out
+    let message = parse("[50,1,{}]").unwrap();
+    match &message {
+        WampMessage::Result { args, kwargs, .. } => assert!(args.is_empty() && kwargs.is_empty()),
+        other => panic!("expected a result, got {:?}", other),
+    }
+    assert_eq!(json(message.into_data()), "[50,1,{}]");
+    assert_eq!(round_trip(r#"[50,1,{},[],{}]"#), "[50,1,{}]");
+    // Args stay when only kwargs are given, as positions matter
+    unchanged(r#"[50,1,{},[],{"a":1}]"#);
+}
+
+#[test]
+fn built_messages() {
+    let message = WampMessage::Call {
+        request_id: 3,
+        options: WampHash::new(),
+        procedure: "a.proc".to_string(),
+        args: vec![1.into()],
+        kwargs: WampHash::new(),
+    };
+    assert_eq!(message.name(), "CALL");
+    assert_eq!(message.request_id(), Some(3));
+    assert_eq!(json(WampData::from(message)), "[48,3,{},\"a.proc\",[1]]");
+
+    let message = WampMessage::Welcome { session_id: 1, details: WampHash::new() };
+    assert_eq!(message.request_id(), None);
+}
+
+#[test]
+fn malformed_messages() {
+    assert!(malformed(r#"{"type":1}"#).contains("should be a list"));
+    assert!(malformed(r#"[]"#).contains("message type"));
+    assert!(malformed(r#"["HELLO","realm1",{}]"#).contains("message type"));
+    assert_eq!(malformed(r#"[99,1]"#), "unknown message type 99");
+    assert!(malformed(r#"[1,"realm1"]"#).contains("missing details at position 2"));
+    assert!(malformed(r#"[2,"session",{}]"#).contains("session should be an id"));
+    assert!(malformed(r#"[1,1,{}]"#).contains("realm should be a string"));
+    assert!(malformed(r#"[2,1,[]]"#).contains("details should be a dict"));
+    assert!(malformed(r#"[50,1,{},"x"]"#).contains("args should be a list"));
+    assert!(malformed(r#"[50,1,{},[],[]]"#).contains("kwargs should be a dict"));
+    assert!(malformed(r#"[36,1]"#).starts_with("EVENT: "));
+}
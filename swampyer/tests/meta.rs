@@ -0,0 +1,81 @@
+#![cfg(feature = "smol")]
+
+mod common;
+
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use swampyer::{wdata, ReconnectPolicy, TestamentBuilder, TestamentScope, WampClient, WampError};
+use common::{run, until, Options, Router};
+
+fn router_without(role:&'static str, feature:&'static str) -> Router {
+    Router::start_with(Options { without: vec![(role, feature)], ..Options::default() })
+}
+
+fn unsupported<T: std::fmt::Debug>(result:Result<T, WampError>, feature:&str) -> bool {
+    matches!(result, Err(WampError::FeatureNotSupported(ref f)) if f == feature)
+}
+
+fn testaments(router:&Router) -> Vec<Value> {
+    router.received(48).into_iter().filter(|call| call[3] == "wamp.session.add_testament").collect()
+}
+
+#[test]
+fn session_events_are_checked_against_the_broker() {
+    let router = router_without("dealer", "session_meta_api");
+    run(router.builder(), |mut client:WampClient| async move {
+        client.on_session_join(|_, _| async {}).await.unwrap();
+        client.on_session_leave(|_, _| async {}).await.unwrap();
+        assert!(unsupported(client.session_count(&[]).await, "session_meta_api"));
+    });
+
+    let router = router_without("broker", "session_meta_api");
+    run(router.builder(), |mut client:WampClient| async move {
+        assert!(unsupported(client.on_session_join(|_, _| async {}).await, "session_meta_api"));
+        assert!(unsupported(client.on_session_leave(|_, _| async {}).await, "session_meta_api"));
+    });
+}
+
+#[test]
+fn session_calls_are_checked_against_the_dealer() {
+    let router = router_without("broker", "session_meta_api");
+    run(router.builder(), |mut client:WampClient| async move {
+        // Gets as far as the router, which doesn't implement it
+        let result = client.session_count(&[]).await;
+        assert!(matches!(result, Err(WampError::CallFailed(ref e)) if e.error == "wamp.error.no_such_procedure"), "{:?}", result);
+    });
+}
+
+#[test]
+fn registration_events_are_checked_against_the_dealer() {
+    let router = router_without("dealer", "registration_meta_api");
+    run(router.builder(), |mut client:WampClient| async move {
+        assert!(unsupported(client.on_registration_create(|_, _| async {}).await, "registration_meta_api"));
+    });
+}
+
+#[test]
+fn testaments_are_added_again_after_a_reconnect() {
+    let router = Router::start();
+    let builder = router.builder()
+                        .reconnect(ReconnectPolicy::exponential(Duration::from_millis(50), Duration::from_millis(100)));
+    run(builder, {
+        let router = router.clone();
+        move |mut client:WampClient| async move {
+            let testament = TestamentBuilder::default()
+                                .topic("test.gone")
+                                .args(wdata!(["gone"]))
+                                .scope(TestamentScope::Detached)
+                                .build()
+                                .unwrap();
+            client.add_testament(testament).await.unwrap();
+            // Fails once the router drops us
+            let _ = client.call("test.drop", wdata!(["test.elsewhere"]), wdata!({})).await;
+            until("the testament to be added again", || testaments(&router).len() == 2).await;
+        }
+    });
+    let expected = json!(["wamp.session.add_testament", ["test.gone", ["gone"], {}], {"publish_options": {}, "scope": "detached"}]);
+    for call in testaments(&router) {
+        assert_eq!(json!(call.as_array().unwrap()[3..]), expected);
+    }
+}
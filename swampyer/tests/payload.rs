@@ -0,0 +1,74 @@
+#![cfg(feature = "smol")]
+
+mod common;
+
+use serde_json::{json, Value};
+use swampyer::{wdata, CallOptionsBuilder, PayloadCodec, PptOptions, PptOptionsBuilder, PublishOptionsBuilder, WampClient, WampData, WampError, Yield};
+use common::{run, Router};
+
+#[test]
+fn encryption_without_a_codec_is_refused() {
+    let router = Router::start();
+    let (call, publish) = run(router.builder(), |mut client:WampClient| async move {
+        let options = CallOptionsBuilder::default().ppt(PptOptions::e2ee("key")).build().unwrap();
+        let call = client.call_with("test.echo", wdata!(["secret"]), wdata!({}), options).await;
+        let options = PublishOptionsBuilder::default().ppt(PptOptions::e2ee("key")).build().unwrap();
+        let publish = client.publish_with("test.topic", wdata!(["secret"]), wdata!({}), options).await;
+        (call, publish)
+    });
+    assert!(matches!(call, Err(WampError::PayloadError(_))), "{:?}", call);
+    assert!(matches!(publish, Err(WampError::PayloadError(_))), "{:?}", publish);
+}
+
+/*
+ * Packs args and kwargs into a single [args, kwargs] payload in the
+ * `x_pack` scheme
+ */
+struct Packing;
+
+impl PayloadCodec for Packing {
+    fn handles(&self, scheme:&str) -> bool {
+        scheme == "x_pack"
+    }
+
+    fn encode(&self, _:&PptOptions, args:&WampData, kwargs:&WampData) -> Result<WampData, WampError> {
+        Ok(WampData::Array(Box::new(vec![args.clone(), kwargs.clone()]), 0))
+    }
+
+    fn decode(&self, _:&PptOptions, payload:&WampData) -> Result<(WampData, WampData), WampError> {
+        match payload {
+            WampData::Array(parts, _) if parts.len() == 2 => Ok((parts[0].clone(), parts[1].clone())),
+            _ => Err(WampError::PayloadError("not packed".to_string())),
+        }
+    }
+}
+
+/// Calls an echo procedure in `scheme` with a Packing codec set,
+/// returning the args the caller got back and those the router saw
+fn echo_in(scheme:&'static str) -> (String, Value) {
+    let router = Router::start();
+    let result = run(router.builder().payload_codec(Packing), move |mut client:WampClient| async move {
+        client.register("test.echo", |_, invocation| async move {
+            Ok(Yield::new(invocation.args, invocation.kwargs))
+        }).await.unwrap();
+        let ppt = PptOptionsBuilder::default().scheme(scheme).build().unwrap();
+        let options = CallOptionsBuilder::default().ppt(ppt).build().unwrap();
+        let result = client.call_with("test.echo", wdata!(["plain"]), wdata!({}), options).await.unwrap();
+        format!("{:?}", result.args)
+    });
+    (result, router.received(48)[0][4].clone())
+}
+
+#[test]
+fn codec_applies_to_the_scheme_it_handles() {
+    let (result, sent) = echo_in("x_pack");
+    assert_eq!(result, "[Str(\"plain\")]");
+    assert_eq!(sent, json!([[["plain"], {}]]));
+}
+
+#[test]
+fn other_schemes_pass_the_codec_by() {
+    let (result, sent) = echo_in("mqtt");
+    assert_eq!(result, "[Str(\"plain\")]");
+    assert_eq!(sent, json!(["plain"]));
+}
@@ -0,0 +1,119 @@
+#![cfg(feature = "smol")]
+
+mod common;
+
+use swampyer::{procedure, wdata, Invocation, Procedure, RpcError, WampClient, WampData, WampError};
+use common::{run, Router};
+
+#[procedure("test.add")]
+async fn add(a:i64, b:i64) -> i64 {
+    a + b
+}
+
+#[procedure("test.greet")]
+async fn greet(invocation:Invocation, name:Option<String>) -> String {
+    format!("{} {}", invocation.procedure, name.as_deref().unwrap_or("nobody"))
+}
+
+#[procedure("test.divide")]
+async fn divide(a:f64, b:f64) -> Result<f64, RpcError> {
+    if b == 0.0 {
+        return Err(RpcError::new("test.error.division_by_zero"));
+    }
+    Ok(a / b)
+}
+
+#[procedure("test.forward")]
+async fn forward(mut client:WampClient) -> Result<i64, WampError> {
+    let result = client.call("test.missing", wdata!([]), wdata!({})).await?;
+    Procedure::result(&result)
+}
+
+#[procedure("test.fail")]
+async fn fail() -> Result<(), WampError> {
+    Err(WampError::Timeout)
+}
+
+#[procedure("test.nothing")]
+async fn nothing() {}
+
+/// Result of calling `uri`, or the error URI and args it failed with
+async fn call(client:&mut WampClient, uri:&str, args:WampData, kwargs:WampData) -> Result<String, String> {
+    match client.call(uri, args, kwargs).await {
+        Ok(result) => Ok(format!("{:?}", result.args)),
+        Err(WampError::CallFailed(e)) => Err(format!("{} {:?}", e.error, e.args)),
+        Err(e) => panic!("call to {} failed: {:?}", uri, e),
+    }
+}
+
+#[test]
+fn procedures_are_collected() {
+    let mut uris = Procedure::all().map(|p| p.uri).collect::<Vec<_>>();
+    uris.sort();
+    assert_eq!(uris, ["test.add", "test.divide", "test.fail", "test.forward", "test.greet", "test.nothing"]);
+}
+
+#[test]
+fn registered_procedures_answer_calls() {
+    let router = Router::start();
+    let results = run(router.builder(), |mut client:WampClient| async move {
+        let registrations = client.register_procedures().await.unwrap();
+        assert_eq!(registrations.len(), 6);
+        vec![
+            call(&mut client, "test.add", wdata!([2, 3]), wdata!({})).await,
+            // Arguments missing from args are read from kwargs
+            call(&mut client, "test.add", wdata!([2]), wdata!({"b": 5})).await,
+            call(&mut client, "test.greet", wdata!([]), wdata!({})).await,
+            call(&mut client, "test.greet", wdata!(["ann"]), wdata!({})).await,
+            call(&mut client, "test.divide", wdata!([1, 4]), wdata!({})).await,
+            call(&mut client, "test.nothing", wdata!([]), wdata!({})).await,
+        ]
+    });
+    assert_eq!(results, [
+        Ok("[UInt(5)]".to_string()),
+        Ok("[UInt(7)]".to_string()),
+        Ok("[Str(\"test.greet nobody\")]".to_string()),
+        Ok("[Str(\"test.greet ann\")]".to_string()),
+        Ok("[Float(0.25)]".to_string()),
+        Ok("[]".to_string()),
+    ]);
+}
+
+#[test]
+fn procedure_errors() {
+    let router = Router::start();
+    let errors = run(router.builder(), |mut client:WampClient| async move {
+        client.register_procedures().await.unwrap();
+        vec![
+            call(&mut client, "test.add", wdata!(["two", 3]), wdata!({})).await,
+            call(&mut client, "test.add", wdata!([1]), wdata!({})).await,
+            call(&mut client, "test.divide", wdata!([1, 0]), wdata!({})).await,
+            // A failed call made inside the procedure is passed on
+            call(&mut client, "test.forward", wdata!([]), wdata!({})).await,
+            call(&mut client, "test.fail", wdata!([]), wdata!({})).await,
+        ]
+    });
+    assert_eq!(errors, [
+        Err("wamp.error.invalid_argument [Str(\"a: expected i64, got Str(\\\"two\\\")\")]".to_string()),
+        Err("wamp.error.invalid_argument [Str(\"b: missing argument\")]".to_string()),
+        Err("test.error.division_by_zero []".to_string()),
+        Err("wamp.error.no_such_procedure []".to_string()),
+        Err("wamp.error.runtime_error [Str(\"Timeout\")]".to_string()),
+    ]);
+}
+
+#[test]
+fn registering_twice_fails() {
+    let router = Router::start();
+    let result = run(router.builder(), |mut client:WampClient| async move {
+        client.register_procedures().await.unwrap();
+        client.register_procedures().await
+    });
+    assert!(result.is_err(), "{:?}", result);
+}
+
+#[test]
+fn compile_errors() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/procedure/*.rs");
+}
@@ -0,0 +1,71 @@
+#![cfg(feature = "smol")]
+
+mod common;
+
+use futures::StreamExt;
+use swampyer::{wdata, CallOptionsBuilder, FromWampData, WampClient, WampData, WampHash, Yield};
+use common::{run, Router};
+
+#[test]
+fn progressive_results_arrive_in_order() {
+    let router = Router::start();
+    let (results, pending) = run(router.builder(), |mut client:WampClient| async move {
+        let options = CallOptionsBuilder::default().receive_progress(true).build().unwrap();
+        let call = client.start_call("test.progress", wdata!([3]), wdata!({}), options).await.unwrap();
+        let results = call.into_stream()
+                          .map(|result| {
+                              let result = result.unwrap();
+                              format!("{} {:?}", result.is_progress(), result.args)
+                          })
+                          .collect::<Vec<_>>()
+                          .await;
+        (results, client.requests_pending().await)
+    });
+    assert_eq!(results, [
+        "true [UInt(0)]",
+        "true [UInt(1)]",
+        "true [UInt(2)]",
+        "false [Str(\"done\")]",
+    ]);
+    assert_eq!(pending, 0);
+}
+
+#[test]
+fn result_skips_progressive_results() {
+    let router = Router::start();
+    let (result, pending) = run(router.builder(), |mut client:WampClient| async move {
+        let options = CallOptionsBuilder::default().receive_progress(true).build().unwrap();
+        let result = client.call_with("test.progress", wdata!([2]), wdata!({}), options).await.unwrap();
+        (format!("{:?}", result.args), client.requests_pending().await)
+    });
+    assert_eq!(result, "[Str(\"done\")]");
+    assert_eq!(pending, 0);
+}
+
+#[test]
+fn progressive_invocations_stream_to_the_handler() {
+    let router = Router::start();
+    let (result, pending) = run(router.builder(), |mut client:WampClient| async move {
+        client.register("test.collect", |_, invocation| async move {
+            let chunks = invocation.into_stream()
+                                   .map(|chunk| {
+                                       // A chunk's letter, marked with + when more follow
+                                       let letter = String::from_wamp_data(&chunk.args[0]).unwrap();
+                                       if chunk.is_progress() { letter + "+" } else { letter }
+                                   })
+                                   .collect::<Vec<_>>()
+                                   .await;
+            Ok(Yield::new(chunks.into_iter().map(WampData::from).collect(), WampHash::new()))
+        }).await.unwrap();
+
+        let options = CallOptionsBuilder::default().progress(true).build().unwrap();
+        let mut call = client.start_call("test.collect", wdata!(["a"]), wdata!({}), options).await.unwrap();
+        call.send_progress(wdata!(["b"]), wdata!({})).await.unwrap();
+        call.send_final(wdata!(["c"]), wdata!({})).await.unwrap();
+        let result = call.result().await.unwrap();
+        (format!("{:?}", result.args), client.invocations_pending().await)
+    });
+    // The stream ends with the final chunk, which lets the handler answer
+    assert_eq!(result, "[Str(\"a+\"), Str(\"b+\"), Str(\"c\")]");
+    assert_eq!(pending, 0);
+}
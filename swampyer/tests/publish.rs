@@ -0,0 +1,65 @@
+#![cfg(feature = "smol")]
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use serde_json::json;
+use swampyer::{wdata, Event, PublishOptionsBuilder, WampClient};
+use common::{run, until, Router};
+
+#[test]
+fn publish_options_reach_the_router() {
+    let router = Router::start();
+    let (session_id, publication, event) = run(router.builder(), |mut client:WampClient| async move {
+        let seen:Arc<Mutex<Vec<Event>>> = Arc::default();
+        {
+            let seen = seen.clone();
+            client.subscribe("test.publish", move |_, event| {
+                seen.lock().unwrap().push(event);
+                async {}
+            }).await.unwrap();
+        }
+
+        let options = PublishOptionsBuilder::default()
+                          .exclude_me(false)
+                          .exclude(vec![7])
+                          .eligible_authrole(vec!["anonymous".to_string()])
+                          .disclose_me(true)
+                          .acknowledge(true)
+                          .build()
+                          .unwrap();
+        let publication = client.publish_with("test.publish", wdata!([1]), wdata!({}), options).await.unwrap();
+        until("the event", || !seen.lock().unwrap().is_empty()).await;
+        let event = seen.lock().unwrap().remove(0);
+        (client.session().await.unwrap().session_id, publication, event)
+    });
+
+    assert_eq!(router.received(16)[0][2], json!({
+        "exclude_me": false,
+        "exclude": [7],
+        "eligible_authrole": ["anonymous"],
+        "disclose_me": true,
+        "acknowledge": true,
+    }));
+    // PUBLISHED hands back the id subscribers see
+    assert_eq!(publication, Some(event.publication_id));
+    assert_eq!(event.publisher, Some(session_id));
+    assert_eq!(event.publisher_authrole.as_deref(), Some("anonymous"));
+}
+
+#[test]
+fn unacknowledged_publish_returns_no_id() {
+    let router = Router::start();
+    let (publication, pending) = run(router.builder(), {
+        let router = router.clone();
+        move |mut client:WampClient| async move {
+            let publication = client.publish("test.publish", wdata!([1]), wdata!({})).await.unwrap();
+            until("the PUBLISH", || !router.received(16).is_empty()).await;
+            (publication, client.requests_pending().await)
+        }
+    });
+    assert_eq!(router.received(16)[0][2], json!({}));
+    assert_eq!(publication, None);
+    assert_eq!(pending, 0);
+}
@@ -0,0 +1,57 @@
+#![cfg(feature = "smol")]
+
+mod common;
+
+use serde_json::json;
+use swampyer::{wdata, CallOptionsBuilder, FromWampData, InvokePolicy, RegisterOptionsBuilder, WampClient, WampData, Yield};
+use common::{run, Router};
+
+#[test]
+fn shared_registration() {
+    let router = Router::start();
+    let invoke = run(router.builder(), |mut client:WampClient| async move {
+        let options = RegisterOptionsBuilder::default().invoke(InvokePolicy::RoundRobin).build().unwrap();
+        client.register_with("test.shared", options, |_, invocation| async move {
+            Ok(Yield::new(vec![invocation.invoke.as_str().into()], Default::default()))
+        }).await.unwrap();
+        let result = client.call("test.shared", wdata!([]), wdata!({})).await.unwrap();
+        format!("{:?}", result.args)
+    });
+    assert_eq!(router.received(64)[0][2], json!({"invoke": "roundrobin"}));
+    // The handler sees the policy it was registered with
+    assert_eq!(invoke, "[Str(\"roundrobin\")]");
+}
+
+#[test]
+fn single_registration_sends_no_invoke_option() {
+    let router = Router::start();
+    let invoke = run(router.builder(), |mut client:WampClient| async move {
+        client.register("test.single", |_, invocation| async move {
+            Ok(Yield::new(vec![invocation.invoke.as_str().into()], Default::default()))
+        }).await.unwrap();
+        let result = client.call("test.single", wdata!([]), wdata!({})).await.unwrap();
+        format!("{:?}", result.args)
+    });
+    assert_eq!(router.received(64)[0][2], json!({}));
+    assert_eq!(invoke, "[Str(\"single\")]");
+}
+
+#[test]
+fn caller_disclosure() {
+    let router = Router::start();
+    let (session_id, disclosed, anonymous) = run(router.builder(), |mut client:WampClient| async move {
+        client.register("test.who", |_, invocation| async move {
+            let info = invocation.info;
+            let who = format!("{:?} {:?} {:?} {}", info.caller, info.caller_authid, info.caller_authrole, info.procedure);
+            Ok(Yield::new(vec![who.into()], Default::default()))
+        }).await.unwrap();
+
+        let options = CallOptionsBuilder::default().disclose_me(true).build().unwrap();
+        let disclosed = client.call_with("test.who", wdata!([]), wdata!({}), options).await.unwrap();
+        let anonymous = client.call("test.who", wdata!([]), wdata!({})).await.unwrap();
+        (client.session().await.unwrap().session_id, disclosed.args, anonymous.args)
+    });
+    let who = |args:&[WampData]| String::from_wamp_data(&args[0]).unwrap();
+    assert_eq!(who(&disclosed), format!("Some({}) Some(\"anonymous\") Some(\"anonymous\") test.who", session_id));
+    assert_eq!(who(&anonymous), "None None None test.who");
+}
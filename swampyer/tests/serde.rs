@@ -0,0 +1,106 @@
+#![cfg(feature = "serde")]
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use swampyer::{from_wamp_data, to_wamp_data, WampData, WampError};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Shape {
+    name: String,
+    points: Vec<Point>,
+    color: Option<String>,
+    #[serde(rename = "closed_path")]
+    closed: bool,
+    tags: BTreeMap<String, u64>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum Command {
+    Stop,
+    Move(Point),
+    Resize(u32, u32),
+    Rename { from: String, to: String },
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Meters(f64);
+
+/// Converts to WampData, checks its JSON form and converts back
+fn round_trip<T>(value:&T, expected:&str) -> T
+where
+    T: Serialize + serde::de::DeserializeOwned,
+{
+    let data = to_wamp_data(value).unwrap();
+    assert_eq!(data.to_json().to_string(), expected);
+    from_wamp_data(data).unwrap()
+}
+
+#[test]
+fn structs() {
+    let point = Point { x: 1, y: -2 };
+    assert_eq!(round_trip(&point, "{\"x\":1,\"y\":-2}"), point);
+
+    let shape = Shape {
+        name: "line".to_string(),
+        points: vec![Point { x: 0, y: 0 }, Point { x: 3, y: 4 }],
+        color: None,
+        closed: false,
+        tags: BTreeMap::from([("layer".to_string(), 2)]),
+    };
+    let expected = "{\"closed_path\":false,\"color\":null,\"name\":\"line\",\"points\":[{\"x\":0,\"y\":0},{\"x\":3,\"y\":4}],\"tags\":{\"layer\":2}}";
+    assert_eq!(round_trip(&shape, expected), shape);
+}
+
+#[test]
+fn enums() {
+    assert_eq!(round_trip(&Command::Stop, "\"Stop\""), Command::Stop);
+    let moved = Command::Move(Point { x: 1, y: 2 });
+    assert_eq!(round_trip(&moved, "{\"Move\":{\"x\":1,\"y\":2}}"), moved);
+    assert_eq!(round_trip(&Command::Resize(3, 4), "{\"Resize\":[3,4]}"), Command::Resize(3, 4));
+    let renamed = Command::Rename { from: "a".to_string(), to: "b".to_string() };
+    assert_eq!(round_trip(&renamed, "{\"Rename\":{\"from\":\"a\",\"to\":\"b\"}}"), renamed);
+}
+
+#[test]
+fn options() {
+    assert_eq!(round_trip(&Some(5u8), "5"), Some(5u8));
+    assert_eq!(round_trip(&None::<u8>, "null"), None);
+    assert_eq!(round_trip(&vec![Some("a".to_string()), None], "[\"a\",null]"), vec![Some("a".to_string()), None]);
+    // A missing field reads as None
+    let shape:Shape = from_wamp_data(WampData::from_json(&serde_json::json!({
+        "name": "dot", "points": [], "closed_path": true, "tags": {},
+    }))).unwrap();
+    assert_eq!(shape.color, None);
+}
+
+#[test]
+fn newtypes_and_tuples() {
+    assert_eq!(round_trip(&Meters(2.5), "2.5"), Meters(2.5));
+    assert_eq!(round_trip(&(1u8, "two".to_string(), true), "[1,\"two\",true]"), (1u8, "two".to_string(), true));
+}
+
+#[test]
+fn wamp_data_passes_through() {
+    let data = WampData::from_json(&serde_json::json!({"a": [1, -2, 2.5, "s", null]}));
+    let back:WampData = from_wamp_data(to_wamp_data(&data).unwrap()).unwrap();
+    assert_eq!(back.to_json(), data.to_json());
+}
+
+#[test]
+fn mismatched_types() {
+    let data = WampData::from_json(&serde_json::json!({"x": "one", "y": 2}));
+    match from_wamp_data::<Point>(data) {
+        Err(WampError::SerdeError(reason)) => assert!(reason.contains("invalid type"), "{}", reason),
+        other => panic!("expected a serde error, got {:?}", other),
+    }
+    let data = WampData::from_json(&serde_json::json!({"Stop": null, "Move": null}));
+    assert!(from_wamp_data::<Command>(data).is_err());
+    assert!(to_wamp_data(&u128::MAX).is_err());
+}
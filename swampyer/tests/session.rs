@@ -0,0 +1,38 @@
+#![cfg(feature = "smol")]
+
+mod common;
+
+use std::time::Duration;
+
+use serde_json::json;
+use swampyer::{wdata, ReconnectPolicy, WampError};
+use common::{until, Options, Router};
+
+#[test]
+fn abort_ends_run_without_reconnecting() {
+    let router = Router::start_with(Options { abort: Some("wamp.error.no_such_realm".to_string()), ..Options::default() });
+    let mut client = router.builder()
+                           .reconnect(ReconnectPolicy::exponential(Duration::from_millis(50), Duration::from_millis(100)))
+                           .build()
+                           .unwrap();
+    let result = smol::block_on(client.run());
+    assert!(matches!(result, Err(WampError::Aborted(ref reason)) if reason == "wamp.error.no_such_realm"), "{:?}", result);
+    assert_eq!(router.received(1).len(), 1);
+}
+
+#[test]
+fn goodbye_from_the_router_is_answered() {
+    let router = Router::start();
+    let mut client = router.builder().build().unwrap();
+    let result = smol::block_on(async {
+        client.onjoin(|mut client, _| async move {
+            let _ = client.call("test.goodbye", wdata!([]), wdata!({})).await;
+        }).await;
+        let result = client.run().await;
+        until("our GOODBYE", || !router.received(6).is_empty()).await;
+        result
+    });
+    // The session is over and the default policy doesn't reconnect
+    assert!(matches!(result, Err(WampError::ConnectionFailure)), "{:?}", result);
+    assert_eq!(router.received(6), [json!([6, {}, "wamp.close.goodbye_and_out"])]);
+}
@@ -0,0 +1,108 @@
+#![cfg(feature = "smol")]
+
+mod common;
+
+use std::sync::Mutex;
+
+use swampyer::{subscribe, wdata, Event, PublishOptionsBuilder, Subscriber, WampClient, WampData};
+use common::{run, until, Router};
+
+// Handlers are plain fns so they report through a global. Each test
+// publishes on its own topics and only looks at those.
+static SEEN:Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+fn record(line:String) {
+    SEEN.lock().unwrap().push(line);
+}
+
+fn seen(prefix:&str) -> Vec<String> {
+    let mut seen = SEEN.lock().unwrap().iter().filter(|l| l.starts_with(prefix)).cloned().collect::<Vec<_>>();
+    seen.sort();
+    seen
+}
+
+#[subscribe("test.temperature")]
+async fn temperature(sensor:String, celsius:f64) {
+    record(format!("temperature {} {}", sensor, celsius));
+}
+
+#[subscribe("test.reading")]
+async fn reading(event:Event, _client:WampClient, value:Option<u64>) {
+    record(format!("reading {} {:?}", event.topic, value));
+}
+
+async fn publish(client:&mut WampClient, topic:&str, args:WampData, kwargs:WampData) {
+    let options = PublishOptionsBuilder::default().exclude_me(false).build().unwrap();
+    client.publish_with(topic, args, kwargs, options).await.unwrap();
+}
+
+#[test]
+fn subscribers_are_collected() {
+    let mut topics = Subscriber::all().map(|s| s.topic).collect::<Vec<_>>();
+    topics.sort();
+    assert_eq!(topics, ["test.reading", "test.temperature"]);
+}
+
+#[test]
+fn events_are_decoded_into_parameters() {
+    let router = Router::start();
+    run(router.builder(), |mut client:WampClient| async move {
+        assert_eq!(client.subscribe_handlers().await.unwrap().len(), 2);
+        publish(&mut client, "test.temperature", wdata!(["kitchen", 21.5]), wdata!({})).await;
+        // Parameters missing from args are read from kwargs
+        publish(&mut client, "test.temperature", wdata!([]), wdata!({"sensor": "hall", "celsius": 18})).await;
+        publish(&mut client, "test.reading", wdata!([7]), wdata!({})).await;
+        // Option parameters may be left out
+        publish(&mut client, "test.reading", wdata!([]), wdata!({})).await;
+        until("all four events", || seen("temperature").len() == 2 && seen("reading").len() == 2).await;
+    });
+    assert_eq!(seen("temperature"), ["temperature hall 18", "temperature kitchen 21.5"]);
+    assert_eq!(seen("reading"), ["reading test.reading None", "reading test.reading Some(7)"]);
+}
+
+#[test]
+fn malformed_events_go_to_onevent_error() {
+    let router = Router::start();
+    run(router.builder(), |mut client:WampClient| async move {
+        client.onevent_error(|_, event, error| {
+            record(format!("error {} {}", event.topic, error));
+            async {}
+        }).await;
+        client.subscribe_handlers().await.unwrap();
+        publish(&mut client, "test.temperature", wdata!(["attic", "hot"]), wdata!({})).await;
+        publish(&mut client, "test.temperature", wdata!(["attic"]), wdata!({})).await;
+        until("both errors", || seen("error test.temperature").len() == 2).await;
+    });
+    assert_eq!(seen("error"), [
+        "error test.temperature celsius: expected f64, got Str(\"hot\")",
+        "error test.temperature celsius: missing argument",
+    ]);
+    assert!(!seen("temperature").iter().any(|l| l.contains("attic")));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn typed_subscriptions_share_onevent_error() {
+    let router = Router::start();
+    run(router.builder(), |mut client:WampClient| async move {
+        client.onevent_error(|_, event, _| {
+            record(format!("error {} {:?}", event.topic, event.args));
+            async {}
+        }).await;
+        client.subscribe_typed("test.typed", |_, (name, count):(String, u32)| {
+            record(format!("typed {} {}", name, count));
+            async {}
+        }).await.unwrap();
+        publish(&mut client, "test.typed", wdata!(["apples", 3]), wdata!({})).await;
+        publish(&mut client, "test.typed", wdata!(["pears", "many"]), wdata!({})).await;
+        until("both events", || seen("typed").len() + seen("error test.typed").len() == 2).await;
+    });
+    assert_eq!(seen("typed"), ["typed apples 3"]);
+    assert_eq!(seen("error test.typed"), ["error test.typed [Str(\"pears\"), Str(\"many\")]"]);
+}
+
+#[test]
+fn compile_errors() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/subscribe/*.rs");
+}
@@ -0,0 +1,39 @@
+#![cfg(feature = "smol")]
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use swampyer::{wdata, Event, PublishOptionsBuilder, WampClient};
+use common::{run, until, Router};
+
+type Seen = Arc<Mutex<Vec<String>>>;
+
+fn record(seen:&Seen, name:&str, event:Event) {
+    seen.lock().unwrap().push(format!("{} {:?}", name, event.args));
+}
+
+#[test]
+fn same_topic_twice_keeps_both_handlers() {
+    let router = Router::start();
+    let seen = run(router.builder(), |mut client:WampClient| async move {
+        let seen = Seen::default();
+        let first = {
+            let seen = seen.clone();
+            client.subscribe("test.topic", move |_, event| { record(&seen, "first", event); async {} }).await.unwrap()
+        };
+        let second = {
+            let seen = seen.clone();
+            client.subscribe("test.topic", move |_, event| { record(&seen, "second", event); async {} }).await.unwrap()
+        };
+        assert_eq!(first, second);
+
+        let options = PublishOptionsBuilder::default().exclude_me(false).build().unwrap();
+        client.publish_with("test.topic", wdata!([1]), wdata!({}), options).await.unwrap();
+        until("both handlers", || seen.lock().unwrap().len() == 2).await;
+        let mut seen = seen.lock().unwrap().clone();
+        seen.sort();
+        seen
+    });
+    assert_eq!(seen, vec!["first [UInt(1)]", "second [UInt(1)]"]);
+}
@@ -0,0 +1,32 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+use std::time::Duration;
+
+use swampyer::{wdata, TokioRuntime, Yield};
+use common::Router;
+
+#[tokio::test]
+async fn session_on_tokio() {
+    let router = Router::start();
+    let mut client = router.builder().runtime(TokioRuntime::new().unwrap()).build().unwrap();
+
+    let (sender, receiver) = async_channel::bounded(1);
+    client.onjoin(move |mut client, _| {
+        let sender = sender.clone();
+        async move {
+            client.register("test.echo", |_, invocation| async move {
+                Ok(Yield::new(invocation.args, invocation.kwargs))
+            }).await.unwrap();
+            let result = client.call("test.echo", wdata!(["tokio"]), wdata!({})).await;
+            let _ = sender.send(result.map(|result| format!("{:?}", result.args))).await;
+        }
+    }).await;
+
+    let result = tokio::select! {
+        result = client.run() => panic!("connection ended: {:?}", result),
+        result = tokio::time::timeout(Duration::from_secs(20), receiver.recv()) => result.expect("timed out"),
+    };
+    assert_eq!(result.unwrap().unwrap(), "[Str(\"tokio\")]");
+}
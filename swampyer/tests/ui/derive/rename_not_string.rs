@@ -0,0 +1,9 @@
+use swampyer::Wamp;
+
+#[derive(Wamp)]
+struct Point {
+    #[wamp(rename = 1)]
+    x: i64,
+}
+
+fn main() {}
@@ -0,0 +1,7 @@
+use swampyer::Wamp;
+
+#[derive(Wamp)]
+#[wamp(kwargs)]
+struct Pair(i64, i64);
+
+fn main() {}
@@ -0,0 +1,9 @@
+use swampyer::Wamp;
+
+#[derive(Wamp)]
+union Number {
+    int: i64,
+    float: f64,
+}
+
+fn main() {}
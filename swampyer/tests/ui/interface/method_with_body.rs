@@ -0,0 +1,10 @@
+use swampyer::interface;
+
+#[interface(prefix = "test.thermostat")]
+trait Thermostat {
+    async fn target(&self) -> f64 {
+        20.0
+    }
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use swampyer::interface;
+
+#[interface]
+trait Thermostat {
+    async fn target(&self) -> f64;
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use swampyer::interface;
+
+#[interface(prefix = "test.thermostat")]
+trait Thermostat {
+    const UNIT:&'static str;
+    async fn target(&self) -> f64;
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use swampyer::interface;
+
+#[interface(prefix = "test.thermostat")]
+trait Thermostat {
+    fn target(&self) -> f64;
+}
+
+fn main() {}
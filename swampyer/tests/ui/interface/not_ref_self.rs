@@ -0,0 +1,8 @@
+use swampyer::interface;
+
+#[interface(prefix = "test.thermostat")]
+trait Thermostat {
+    async fn set_target(&mut self, celsius:f64);
+}
+
+fn main() {}
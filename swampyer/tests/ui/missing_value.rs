@@ -0,0 +1,5 @@
+use swampyer::wdata;
+
+fn main() {
+    let _ = wdata!({"a": 1, "b":});
+}
@@ -0,0 +1,8 @@
+use swampyer::procedure;
+
+#[procedure("test.echo")]
+async fn echo<T>(value:T) -> T {
+    value
+}
+
+fn main() {}
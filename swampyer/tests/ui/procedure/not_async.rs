@@ -0,0 +1,8 @@
+use swampyer::procedure;
+
+#[procedure("test.add")]
+fn add(a:i64, b:i64) -> i64 {
+    a + b
+}
+
+fn main() {}
@@ -0,0 +1,12 @@
+use swampyer::procedure;
+
+struct Counter;
+
+impl Counter {
+    #[procedure("test.count")]
+    async fn count(&self) -> u64 {
+        1
+    }
+}
+
+fn main() {}
@@ -0,0 +1,6 @@
+use swampyer::subscribe;
+
+#[subscribe("test.temperature")]
+async fn temperature<T>(celsius:T) {}
+
+fn main() {}
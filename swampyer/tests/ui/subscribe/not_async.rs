@@ -0,0 +1,6 @@
+use swampyer::subscribe;
+
+#[subscribe("test.temperature")]
+fn temperature(celsius:f64) {}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use swampyer::subscribe;
+
+#[subscribe("test.temperature")]
+async fn temperature(celsius:f64) -> bool {
+    celsius > 20.0
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use swampyer::subscribe;
+
+struct Sensor;
+
+impl Sensor {
+    #[subscribe("test.temperature")]
+    async fn temperature(&self, celsius:f64) {}
+}
+
+fn main() {}
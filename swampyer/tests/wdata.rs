@@ -0,0 +1,101 @@
+use swampyer::{wdata, WampArray, WampData, WampHash};
+
+fn json(data:WampData) -> String {
+    data.to_json().to_string()
+}
+
+#[test]
+fn empty() {
+    assert_eq!(json(wdata!([])), "[]");
+    assert_eq!(json(wdata!({})), "{}");
+    assert_eq!(json(wdata!(null)), "null");
+}
+
+#[test]
+fn scalars() {
+    assert_eq!(json(wdata!(1)), "1");
+    assert_eq!(json(wdata!(-1)), "-1");
+    assert_eq!(json(wdata!(2.5)), "2.5");
+    assert_eq!(json(wdata!(true)), "true");
+    assert_eq!(json(wdata!("text")), "\"text\"");
+}
+
+#[test]
+fn arrays() {
+    assert_eq!(json(wdata!([1, "two", 3.5, false, null])), "[1,\"two\",3.5,false,null]");
+    assert_eq!(json(wdata!([1, 2,])), "[1,2]");
+    assert_eq!(json(wdata!([[1, [2]], {"a": []}])), "[[1,[2]],{\"a\":[]}]");
+}
+
+#[test]
+fn dicts() {
+    assert_eq!(json(wdata!({"a": 1})), "{\"a\":1}");
+    assert_eq!(json(wdata!({"a": 1,})), "{\"a\":1}");
+    assert_eq!(json(wdata!({"a": 1, "b": [true, null]})), "{\"a\":1,\"b\":[true,null]}");
+    assert_eq!(json(wdata!({"a": {"b": {"c": null}}})), "{\"a\":{\"b\":{\"c\":null}}}");
+}
+
+#[test]
+fn expressions() {
+    let a = 2;
+    let name = String::from("x");
+    let list:WampArray = vec![1.into(), 2.into()];
+    assert_eq!(json(wdata!([a + 1, a * 2, name.clone(), (a - 1)])), "[3,4,\"x\",1]");
+    assert_eq!(json(wdata!([name.as_str(), list.clone()])), "[\"x\",[1,2]]");
+    assert_eq!(json(wdata!({"sum": a + 1, "name": name.len() as u64, "list": list})), "{\"list\":[1,2],\"name\":1,\"sum\":3}");
+    assert_eq!(json(wdata!([-a, name.len() as u32 + 2, vec![1u8, 2].len() as u32])), "[-2,3,2]");
+}
+
+#[test]
+fn keys() {
+    let key = "dynamic";
+    let owned = String::from("owned");
+    let prefix = "com.example";
+    assert_eq!(json(wdata!({key: 1})), "{\"dynamic\":1}");
+    assert_eq!(json(wdata!({owned: 1, key: 2,})), "{\"dynamic\":2,\"owned\":1}");
+    assert_eq!(json(wdata!({format!("{}.add", prefix): 1})), "{\"com.example.add\":1}");
+    assert_eq!(json(wdata!({1: "one", (key.len()): "len"})), "{\"1\":\"one\",\"7\":\"len\"}");
+}
+
+#[test]
+fn spreading() {
+    let rest:WampArray = vec![2.into(), 3.into()];
+    assert_eq!(json(wdata!([1, ..rest.clone(), 4])), "[1,2,3,4]");
+    assert_eq!(json(wdata!([..rest.clone()])), "[2,3]");
+    assert_eq!(json(wdata!([..vec![1, 2], ..rest,])), "[1,2,2,3]");
+
+    let mut defaults = WampHash::new();
+    defaults.insert("a".to_string(), Box::new(1.into()));
+    defaults.insert("b".to_string(), Box::new(2.into()));
+    assert_eq!(json(wdata!({..defaults.clone(), "b": 3, "c": 4})), "{\"a\":1,\"b\":3,\"c\":4}");
+    assert_eq!(json(wdata!({"b": 3, ..defaults.clone(),})), "{\"a\":1,\"b\":2}");
+    assert_eq!(json(wdata!({..defaults})), "{\"a\":1,\"b\":2}");
+}
+
+#[test]
+fn immutable_binding() {
+    // Dicts without a trailing comma used to need a mutable binding
+    let data = wdata!({"k": 1});
+    assert_eq!(json(data), "{\"k\":1}");
+}
+
+#[test]
+fn long_literals() {
+    let data = wdata!([
+        0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9,
+        0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9,
+        0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9,
+        0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9,
+        0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9,
+    ]);
+    match data {
+        WampData::Array(a, _) => assert_eq!(a.len(), 150),
+        other => panic!("expected an array, got {:?}", other),
+    }
+}
+
+#[test]
+fn compile_errors() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/*.rs");
+}
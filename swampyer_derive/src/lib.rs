@@ -5,22 +5,349 @@ extern crate syn;
 extern crate proc_macro;
 
 use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
 
-use quote::quote;
-use syn::{parse_macro_input, DeriveInput};
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, parse_quote, Attribute, Data, DeriveInput, Fields, Generics, Ident, Lit, Member, Meta, NestedMeta};
 
-#[proc_macro_derive(Wamp)]
-pub fn derive_decode_fn(input: TokenStream) -> TokenStream {
-    let input = parse_macro_input!(input as DeriveInput);
+/*
+ * How a struct or variant with fields is laid out as WampData
+ */
+#[derive(Clone, Copy, PartialEq)]
+enum Layout {
+    /// An array in field order, as positional args
+    Args,
+    /// A dict keyed by field name, as keyword args
+    Kwargs,
+}
+
+#[derive(Default)]
+struct Attrs {
+    layout: Option<Layout>,
+    rename: Option<String>,
+    default: bool,
+    skip: bool,
+    optional: bool,
+    into: bool,
+}
+
+/// Reads every `#[wamp(...)]` on an item, field or variant
+fn parse_attrs(attrs:&[Attribute]) -> syn::Result<Attrs> {
+    let mut parsed = Attrs::default();
+    for attr in attrs.iter().filter(|a| a.path.is_ident("wamp")) {
+        let list = match attr.parse_meta()? {
+            Meta::List(list) => list,
+            meta => return Err(syn::Error::new_spanned(meta, "expected #[wamp(...)]")),
+        };
+        for nested in list.nested.iter() {
+            match nested {
+                NestedMeta::Meta(Meta::Path(p)) if p.is_ident("args") => parsed.layout = Some(Layout::Args),
+                NestedMeta::Meta(Meta::Path(p)) if p.is_ident("kwargs") => parsed.layout = Some(Layout::Kwargs),
+                NestedMeta::Meta(Meta::Path(p)) if p.is_ident("default") => parsed.default = true,
+                NestedMeta::Meta(Meta::Path(p)) if p.is_ident("skip") => parsed.skip = true,
+                NestedMeta::Meta(Meta::Path(p)) if p.is_ident("optional") => parsed.optional = true,
+                NestedMeta::Meta(Meta::Path(p)) if p.is_ident("into") => parsed.into = true,
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("rename") => {
+                    match nv.lit {
+                        Lit::Str(ref s) => parsed.rename = Some(s.value()),
+                        ref lit => return Err(syn::Error::new_spanned(lit, "rename takes a string")),
+                    }
+                },
+                other => return Err(syn::Error::new_spanned(other, "unknown wamp attribute")),
+            }
+        }
+    }
+    Ok(parsed)
+}
+
+struct FieldInfo {
+    member: Member,
+    /// Variable the field is bound to when destructuring
+    binding: Ident,
+    /// Key in the kwargs layout
+    key: String,
+    attrs: Attrs,
+}
+
+fn field_infos(fields:&Fields) -> syn::Result<Vec<FieldInfo>> {
+    fields.iter().enumerate().map(|(i, field)| {
+        let attrs = parse_attrs(&field.attrs)?;
+        let (member, name) = match field.ident {
+            Some(ref ident) => (Member::Named(ident.clone()), ident.to_string()),
+            None => (Member::Unnamed(i.into()), i.to_string()),
+        };
+        Ok(FieldInfo {
+            member,
+            binding: format_ident!("__field_{}", i),
+            key: attrs.rename.clone().unwrap_or(name),
+            attrs,
+        })
+    }).collect()
+}
+
+/// `Path { a: __field_0, 0: __field_1, .. }`, which works for named and
+/// tuple fields alike, both as a pattern and as a constructor
+fn destructure(path:&TokenStream2, fields:&[FieldInfo]) -> TokenStream2 {
+    let members = fields.iter().map(|f| &f.member);
+    let bindings = fields.iter().map(|f| &f.binding);
+    quote! { #path { #( #members: #bindings ),* } }
+}
+
+/// Builds WampData out of the bound fields
+fn encode_fields(fields:&[FieldInfo], layout:Layout) -> TokenStream2 {
+    let fields = fields.iter().filter(|f| !f.attrs.skip);
+    match layout {
+        Layout::Args => {
+            let fields = fields.collect::<Vec<_>>();
+            let bindings = fields.iter().map(|f| &f.binding);
+            let optional = fields.iter().map(|f| f.attrs.optional);
+            quote! {
+                {
+                    let mut array = ::swampyer::WampArray::new();
+                    #( array.push(::swampyer::ToWampData::to_wamp_data(#bindings)); )*
+                    // Optional args left unset at the end are not sent
+                    let optional = [ #( #optional ),* ];
+                    while array.last().is_some_and(|v| matches!(v, ::swampyer::WampData::None)) && optional[array.len() - 1] {
+                        array.pop();
+                    }
+                    ::swampyer::WampData::Array(Box::new(array), 0)
+                }
+            }
+        },
+        Layout::Kwargs => {
+            let inserts = fields.map(|f| {
+                let binding = &f.binding;
+                let key = &f.key;
+                if f.attrs.optional {
+                    quote! {
+                        let value = ::swampyer::ToWampData::to_wamp_data(#binding);
+                        if !matches!(value, ::swampyer::WampData::None) {
+                            hash.insert(#key.to_string(), Box::new(value));
+                        }
+                    }
+                }
+                else {
+                    quote! {
+                        hash.insert(#key.to_string(), Box::new(::swampyer::ToWampData::to_wamp_data(#binding)));
+                    }
+                }
+            });
+            quote! {
+                {
+                    let mut hash = ::swampyer::WampHash::new();
+                    #( #inserts )*
+                    ::swampyer::WampData::Hash(Box::new(hash), 0)
+                }
+            }
+        },
+    }
+}
+
+/// Binds each field from `data` then builds `path` out of them
+fn decode_fields(path:&TokenStream2, fields:&[FieldInfo], layout:Layout, owner:&str, data:TokenStream2) -> TokenStream2 {
+    let mut position = 0usize;
+    let reads = fields.iter().map(|f| {
+        let binding = &f.binding;
+        if f.attrs.skip {
+            return quote! { let #binding = ::std::default::Default::default(); };
+        }
+        let key = &f.key;
+        let value = match layout {
+            Layout::Args => {
+                let i = position;
+                position += 1;
+                quote! { container.get(#i) }
+            },
+            Layout::Kwargs => quote! { container.get(#key).map(|v| v.as_ref()) },
+        };
+        if f.attrs.default || f.attrs.optional {
+            quote! { let #binding = ::swampyer::convert::field_or_default(#value, #owner, #key)?; }
+        }
+        else {
+            quote! { let #binding = ::swampyer::convert::field(#value, #owner, #key)?; }
+        }
+    }).collect::<Vec<_>>();
+
+    let container = match layout {
+        Layout::Args => quote! { ::swampyer::convert::expect_array(#data, #owner)? },
+        Layout::Kwargs => quote! { ::swampyer::convert::expect_hash(#data, #owner)? },
+    };
+    let construct = destructure(path, fields);
+    quote! {
+        {
+            let container = #container;
+            #( #reads )*
+            Ok(#construct)
+        }
+    }
+}
+
+/// Layout asked for with `#[wamp(args)]` or `#[wamp(kwargs)]`. Named
+/// fields default to kwargs, tuple fields can only be args.
+fn layout_for(fields:&Fields, attrs:&Attrs, span:&dyn quote::ToTokens) -> syn::Result<Layout> {
+    match (fields, attrs.layout) {
+        (Fields::Unnamed(_), Some(Layout::Kwargs)) => {
+            Err(syn::Error::new_spanned(span, "tuple fields have no names to use as kwargs"))
+        },
+        (Fields::Unnamed(_), _) => Ok(Layout::Args),
+        (_, Some(layout)) => Ok(layout),
+        (_, None) => Ok(Layout::Kwargs),
+    }
+}
+
+fn with_bound(generics:&Generics, bound:TokenStream2) -> Generics {
+    let mut generics = generics.clone();
+    for param in generics.type_params_mut() {
+        param.bounds.push(syn::parse2(bound.clone()).unwrap());
+    }
+    generics
+}
+
+/// The to/from WampData bodies for a struct
+fn struct_bodies(input:&DeriveInput, fields:&Fields) -> syn::Result<(TokenStream2, TokenStream2)> {
+    let owner = input.ident.to_string();
+    let attrs = parse_attrs(&input.attrs)?;
+    if let Fields::Unit = fields {
+        return Ok((quote! { ::swampyer::WampData::None }, quote! { Ok(Self) }));
+    }
+    let layout = layout_for(fields, &attrs, &input.ident)?;
+    let infos = field_infos(fields)?;
+    let path = quote! { Self };
+
+    let pattern = destructure(&path, &infos);
+    let encode = encode_fields(&infos, layout);
+    let decode = decode_fields(&path, &infos, layout, &owner, quote! { &data });
+    Ok((
+        quote! {
+            let #pattern = self;
+            #encode
+        },
+        quote! {
+            let data = ::swampyer::convert::resolve(data)?;
+            #decode
+        },
+    ))
+}
+
+/// The to/from WampData bodies for an enum. Unit variants are their
+/// name, the others `{ "Variant": value }`.
+fn enum_bodies(input:&DeriveInput, data:&syn::DataEnum) -> syn::Result<(TokenStream2, TokenStream2)> {
+    let owner = input.ident.to_string();
+    let mut encode_arms = Vec::new();
+    let mut decode_arms = Vec::new();
 
-    let ident = input.ident;
+    for variant in data.variants.iter() {
+        let attrs = parse_attrs(&variant.attrs)?;
+        let ident = &variant.ident;
+        let name = attrs.rename.clone().unwrap_or_else(|| ident.to_string());
+        let path = quote! { Self::#ident };
+        let variant_owner = format!("{}::{}", owner, ident);
+
+        match variant.fields {
+            Fields::Unit => {
+                encode_arms.push(quote! {
+                    #path => ::swampyer::WampData::Str(#name.to_string()),
+                });
+                decode_arms.push(quote! {
+                    #name => Ok(#path),
+                });
+            },
+            // A single unnamed field stands for the value itself
+            Fields::Unnamed(ref unnamed) if unnamed.unnamed.len() == 1 && attrs.layout.is_none() => {
+                encode_arms.push(quote! {
+                    #path(value) => ::swampyer::convert::variant(#name, ::swampyer::ToWampData::to_wamp_data(value)),
+                });
+                decode_arms.push(quote! {
+                    #name => {
+                        let value = ::swampyer::convert::variant_value(value, #owner, #name)?;
+                        let value = ::swampyer::FromWampData::from_wamp_data(value)
+                                        .map_err(|e| ::swampyer::convert::context(e, #owner, #name))?;
+                        Ok(#path(value))
+                    },
+                });
+            },
+            ref fields => {
+                let layout = layout_for(fields, &attrs, variant)?;
+                let infos = field_infos(fields)?;
+                let pattern = destructure(&path, &infos);
+                let encode = encode_fields(&infos, layout);
+                let decode = decode_fields(&path, &infos, layout, &variant_owner, quote! { value });
+                encode_arms.push(quote! {
+                    #pattern => ::swampyer::convert::variant(#name, #encode),
+                });
+                decode_arms.push(quote! {
+                    #name => {
+                        let value = ::swampyer::convert::variant_value(value, #owner, #name)?;
+                        #decode
+                    },
+                });
+            },
+        }
+    }
+
+    Ok((
+        quote! {
+            match self {
+                #( #encode_arms )*
+            }
+        },
+        quote! {
+            let data = ::swampyer::convert::resolve(data)?;
+            let (name, value) = ::swampyer::convert::enum_variant(&data, #owner)?;
+            match name {
+                #( #decode_arms )*
+                other => Err(::swampyer::convert::unknown_variant(#owner, other)),
+            }
+        },
+    ))
+}
+
+/// The WampSerializable and From<T> for WampData impls. By default the
+/// value is kept as WampData::Serializable and written through its
+/// minicbor Encode impl. With `#[wamp(into)]` it's turned into plain
+/// WampData through ToWampData instead.
+fn into_impls(input:&DeriveInput, into:bool) -> TokenStream2 {
+    let ident = &input.ident;
     let ident_str = ident.to_string();
 
-    let tokens = quote! {
+    if into {
+        let generics = with_bound(&input.generics, quote! { ::swampyer::ToWampData });
+        let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+        return quote! {
+            impl #impl_generics ::swampyer::WampSerializable for #ident #ty_generics #where_clause {
+                fn encode(&self, encoder:&mut ::swampyer::Encoder<&mut ::swampyer::WampWrite> ) {
+                    ::swampyer::ToWampData::to_wamp_data(self).serialize_with(encoder);
+                }
 
-        impl WampSerializable for #ident {
-            fn encode(&self, encoder:&mut Encoder<&mut WampWrite> ) {
-                encoder.encode(self);
+                fn debug_name(&self) -> &str {
+                    #ident_str
+                }
+            }
+
+            impl #impl_generics From< #ident #ty_generics > for ::swampyer::WampData #where_clause {
+                fn from(d: #ident #ty_generics ) -> Self {
+                    ::swampyer::ToWampData::to_wamp_data(&d)
+                }
+            }
+        };
+    }
+
+    let (_, ty_generics, _) = input.generics.split_for_impl();
+    let mut encode_generics = input.generics.clone();
+    encode_generics.make_where_clause().predicates.push(parse_quote! {
+        #ident #ty_generics: ::swampyer::encode::Encode<()>
+    });
+    let (encode_impl, _, encode_where) = encode_generics.split_for_impl();
+    let mut from_generics = input.generics.clone();
+    from_generics.make_where_clause().predicates.push(parse_quote! {
+        #ident #ty_generics: ::swampyer::WampSerializable + Send + Sync + 'static
+    });
+    let (from_impl, _, from_where) = from_generics.split_for_impl();
+    quote! {
+        impl #encode_impl ::swampyer::WampSerializable for #ident #ty_generics #encode_where {
+            fn encode(&self, encoder:&mut ::swampyer::Encoder<&mut ::swampyer::WampWrite> ) {
+                // Writing into a WampWrite can't fail
+                let _ = encoder.encode(self);
             }
 
             fn debug_name(&self) -> &str {
@@ -28,23 +355,471 @@ pub fn derive_decode_fn(input: TokenStream) -> TokenStream {
             }
         }
 
-        /*
-        impl From<Arc< #ident >> for swampyer::WampData {
-            fn from(d:Arc< #ident >) -> Self {
-                swampyer::WampData::Serializable(d)
+        impl #from_impl From< #ident #ty_generics > for ::swampyer::WampData #from_where {
+            fn from(d: #ident #ty_generics ) -> Self {
+                ::swampyer::WampData::Serializable(::std::sync::Arc::new(d))
+            }
+        }
+    }
+}
+
+/*
+ * Derives ToWampData and FromWampData along with the From/TryFrom
+ * conversions to and from WampData. `From<T> for WampData` wraps the
+ * value in WampData::Serializable and needs a minicbor Encode impl,
+ * unless the type is marked `#[wamp(into)]` to convert through
+ * ToWampData.
+ */
+#[proc_macro_derive(Wamp, attributes(wamp))]
+pub fn derive_decode_fn(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    let into = match parse_attrs(&input.attrs) {
+        Ok(attrs) => attrs.into,
+        Err(e) => return TokenStream::from(e.to_compile_error()),
+    };
+    let bodies = match input.data {
+        Data::Struct(ref s) => struct_bodies(&input, &s.fields),
+        Data::Enum(ref e) => enum_bodies(&input, e),
+        Data::Union(_) => Err(syn::Error::new_spanned(&input.ident, "Wamp can't be derived for unions")),
+    };
+    let (encode, decode) = match bodies {
+        Ok(bodies) => bodies,
+        Err(e) => return TokenStream::from(e.to_compile_error()),
+    };
+
+    let ident = &input.ident;
+    let into_impls = into_impls(&input, into);
+
+    let to_generics = with_bound(&input.generics, quote! { ::swampyer::ToWampData });
+    let (to_impl, ty_generics, to_where) = to_generics.split_for_impl();
+    let from_generics = with_bound(&input.generics, quote! { ::swampyer::FromWampData });
+    let (from_impl, _, from_where) = from_generics.split_for_impl();
+    let mut try_generics = from_generics.clone();
+    try_generics.params.insert(0, parse_quote! { '__wamp });
+    let (try_impl, _, _) = try_generics.split_for_impl();
+
+    let tokens = quote! {
+
+        impl #to_impl ::swampyer::ToWampData for #ident #ty_generics #to_where {
+            #[allow(unused_variables)]
+            fn to_wamp_data(&self) -> ::swampyer::WampData {
+                #encode
+            }
+        }
+
+        impl #from_impl ::swampyer::FromWampData for #ident #ty_generics #from_where {
+            #[allow(unused_variables)]
+            fn from_wamp_data(data:&::swampyer::WampData) -> Result<Self, ::swampyer::WampError> {
+                #decode
             }
         }
-        */
 
-        impl From< #ident > for swampyer::WampData {
-            fn from(d: #ident ) -> Self {
-                swampyer::WampData::Serializable(Arc::new(d))
+        impl #try_impl ::std::convert::TryFrom<&'__wamp ::swampyer::WampData> for #ident #ty_generics #from_where {
+            type Error = ::swampyer::WampError;
+
+            fn try_from(data:&'__wamp ::swampyer::WampData) -> Result<Self, ::swampyer::WampError> {
+                ::swampyer::FromWampData::from_wamp_data(data)
             }
         }
+
+        #into_impls
     };
 
     TokenStream::from(tokens)
 }
 
+/// Name of the last segment of a path type, e.g. `Result` for
+/// `std::result::Result<T, E>`
+fn type_name(ty:&syn::Type) -> Option<String> {
+    match ty {
+        syn::Type::Path(p) => p.path.segments.last().map(|s| s.ident.to_string()),
+        _ => None,
+    }
+}
 
+/// Name a parameter is read from kwargs by
+fn param_name(pat:&syn::Pat, position:usize) -> String {
+    match pat {
+        syn::Pat::Ident(p) => p.ident.to_string(),
+        _ => format!("arg{}", position),
+    }
+}
 
+/// Turns the `result` of a procedure returning `output` into the
+/// Result<Yield, RpcError> its handler returns. The Err type may be
+/// anything RpcError converts from, such as RpcError or WampError.
+fn yield_result(output:&syn::ReturnType) -> TokenStream2 {
+    let returns_result = match output {
+        syn::ReturnType::Type(_, ty) => type_name(ty).as_deref() == Some("Result"),
+        syn::ReturnType::Default => false,
+    };
+    if returns_result {
+        quote! {
+            match result {
+                Ok(value) => Ok(::swampyer::Procedure::into_yield(&value)),
+                Err(e) => Err(::swampyer::RpcError::from(e)),
+            }
+        }
+    } else {
+        quote! { Ok(::swampyer::Procedure::into_yield(&result)) }
+    }
+}
+
+fn procedure_tokens(uri:syn::LitStr, item:syn::ItemFn) -> syn::Result<TokenStream2> {
+    let sig = &item.sig;
+    if sig.asyncness.is_none() {
+        return Err(syn::Error::new_spanned(sig.fn_token, "procedures have to be async"));
+    }
+    if !sig.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(&sig.generics, "procedures can't be generic"));
+    }
+
+    // The client and invocation are handed over as is, everything else
+    // is read from the call's args in order
+    let mut params = Vec::new();
+    let mut position = 0usize;
+    for input in &sig.inputs {
+        let arg = match input {
+            syn::FnArg::Typed(arg) => arg,
+            syn::FnArg::Receiver(r) => return Err(syn::Error::new_spanned(r, "procedures can't take self")),
+        };
+        let ty = &arg.ty;
+        match type_name(ty).as_deref() {
+            Some("WampClient") => params.push(quote! { client.clone() }),
+            Some("Invocation") => params.push(quote! { invocation.clone() }),
+            _ => {
+                let name = param_name(&arg.pat, position);
+                params.push(quote! {
+                    ::swampyer::Procedure::argument::< #ty >(&invocation, #position, #name)?
+                });
+                position += 1;
+            },
+        }
+    }
+
+    let ident = &sig.ident;
+    let finish = yield_result(&sig.output);
+
+    Ok(quote! {
+        #item
+
+        const _: () = {
+            fn __swampyer_handler(client: ::swampyer::WampClient, invocation: ::swampyer::Invocation)
+                -> ::swampyer::BoxFuture<Result<::swampyer::Yield, ::swampyer::RpcError>>
+            {
+                Box::pin(async move {
+                    let result = #ident( #(#params),* ).await;
+                    #finish
+                })
+            }
+
+            ::swampyer::inventory::submit! {
+                ::swampyer::Procedure::new(#uri, __swampyer_handler)
+            }
+        };
+    })
+}
+
+/*
+ * Registers an async fn as the procedure `uri`. Its parameters are read
+ * from the INVOCATION's args, falling back to the kwarg of the same
+ * name, and its return value is yielded as the single result. A
+ * `WampClient` or `Invocation` parameter gets the client or the raw
+ * invocation instead. Returning a Result sends any Err as ERROR through
+ * `RpcError::from`, so it may be an RpcError, sent as is, or a
+ * WampError, sent as `wamp.error.runtime_error` unless it's a failed
+ * call whose error is passed on.
+ *
+ *   #[swampyer::procedure("com.example.add")]
+ *   async fn add(a:i64, b:i64) -> i64 { a + b }
+ *
+ * WampClient::register_procedures registers all of them.
+ */
+#[proc_macro_attribute]
+pub fn procedure(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let uri = parse_macro_input!(attr as syn::LitStr);
+    let item = parse_macro_input!(item as syn::ItemFn);
+    match procedure_tokens(uri, item) {
+        Ok(tokens) => TokenStream::from(tokens),
+        Err(e) => TokenStream::from(e.to_compile_error()),
+    }
+}
+
+/// Type a caller gets back from a procedure returning `output`, the Ok
+/// type for a Result
+fn value_type(output:&syn::ReturnType) -> syn::Type {
+    let ty = match output {
+        syn::ReturnType::Type(_, ty) => ty,
+        syn::ReturnType::Default => return parse_quote! { () },
+    };
+    if let syn::Type::Path(ref p) = **ty {
+        let last = p.path.segments.last().unwrap();
+        if last.ident == "Result" {
+            if let syn::PathArguments::AngleBracketed(ref args) = last.arguments {
+                if let Some(syn::GenericArgument::Type(ok)) = args.args.first() {
+                    return ok.clone();
+                }
+            }
+        }
+    }
+    (**ty).clone()
+}
+
+fn interface_tokens(prefix:String, mut item:syn::ItemTrait) -> syn::Result<TokenStream2> {
+    let trait_ident = &item.ident;
+    let vis = &item.vis;
+    let client_ident = format_ident!("{}Client", trait_ident);
+    let server_ident = format_ident!("{}Server", trait_ident);
+
+    let mut stubs = Vec::new();
+    let mut registrations = Vec::new();
+    for trait_item in item.items.iter_mut() {
+        let method = match trait_item {
+            syn::TraitItem::Method(method) => method,
+            other => return Err(syn::Error::new_spanned(other, "interfaces may only contain methods")),
+        };
+        let attrs = parse_attrs(&method.attrs)?;
+        method.attrs.retain(|a| !a.path.is_ident("wamp"));
+        if let Some(ref body) = method.default {
+            return Err(syn::Error::new_spanned(body, "interface methods can't have a body"));
+        }
+
+        let sig = &mut method.sig;
+        if sig.asyncness.is_none() {
+            return Err(syn::Error::new_spanned(sig.fn_token, "interface methods have to be async"));
+        }
+        if !sig.generics.params.is_empty() {
+            return Err(syn::Error::new_spanned(&sig.generics, "interface methods can't be generic"));
+        }
+        let uri = format!("{}.{}", prefix, attrs.rename.unwrap_or_else(|| sig.ident.to_string()));
+
+        let mut inputs = sig.inputs.iter();
+        match inputs.next() {
+            Some(syn::FnArg::Receiver(r)) if r.reference.is_some() && r.mutability.is_none() => (),
+            _ => return Err(syn::Error::new_spanned(&sig.ident, "interface methods have to take &self")),
+        }
+        let mut params = Vec::new();
+        let mut bindings = Vec::new();
+        let mut reads = Vec::new();
+        for (position, input) in inputs.enumerate() {
+            let arg = match input {
+                syn::FnArg::Typed(arg) => arg,
+                syn::FnArg::Receiver(r) => return Err(syn::Error::new_spanned(r, "unexpected self")),
+            };
+            let ty = &arg.ty;
+            let name = param_name(&arg.pat, position);
+            let binding = format_ident!("__arg{}", position);
+            params.push(quote! { #binding: #ty });
+            reads.push(quote! {
+                ::swampyer::Procedure::argument::< #ty >(&invocation, #position, #name)?
+            });
+            bindings.push(binding);
+        }
+
+        let ident = sig.ident.clone();
+        let value = value_type(&sig.output);
+        stubs.push(quote! {
+            pub async fn #ident(&self, #(#params),*) -> Result<#value, ::swampyer::WampError> {
+                let args = vec![ #( ::swampyer::ToWampData::to_wamp_data(&#bindings) ),* ];
+                let result = self.client.clone()
+                                 .call(#uri, ::swampyer::WampData::Array(Box::new(args), 0), ::swampyer::WampData::None)
+                                 .await?;
+                ::swampyer::Procedure::result::< #value >(&result)
+            }
+        });
+
+        let finish = yield_result(&sig.output);
+        registrations.push(quote! {
+            let service = self.service.clone();
+            registrations.push(client.register(#uri, move |_client, invocation: ::swampyer::Invocation| {
+                let service = service.clone();
+                async move {
+                    let result = service.#ident( #(#reads),* ).await;
+                    #finish
+                }
+            }).await?);
+        });
+
+        // The adapter hands these futures to the client's tasks, so
+        // they have to be Send
+        sig.asyncness = None;
+        let output = value_type_or_output(&sig.output);
+        sig.output = parse_quote! {
+            -> impl ::std::future::Future<Output = #output> + Send
+        };
+    }
+
+    let client_doc = format!(" Calls the `{}` procedures on the router", prefix);
+    let server_doc = format!(" Registers an implementation of {} as the `{}` procedures", trait_ident, prefix);
+    Ok(quote! {
+        #item
+
+        #[doc = #client_doc]
+        #[derive(Clone)]
+        #vis struct #client_ident {
+            client: ::swampyer::WampClient,
+        }
+
+        impl #client_ident {
+            pub fn new(client: ::swampyer::WampClient) -> Self {
+                #client_ident { client }
+            }
+
+            #(#stubs)*
+        }
+
+        #[doc = #server_doc]
+        #vis struct #server_ident<T> {
+            service: ::std::sync::Arc<T>,
+        }
+
+        impl<T: #trait_ident + Send + Sync + 'static> #server_ident<T> {
+            pub fn new(service:T) -> Self {
+                #server_ident { service: ::std::sync::Arc::new(service) }
+            }
+
+            /// Registers every method, returning the registration ids in
+            /// the order the trait declares them
+            pub async fn register(&self, client:&mut ::swampyer::WampClient) -> Result<Vec<u64>, ::swampyer::WampError> {
+                let mut registrations = Vec::new();
+                #(#registrations)*
+                Ok(registrations)
+            }
+        }
+    })
+}
+
+/// The type an async fn returning `output` resolves to
+fn value_type_or_output(output:&syn::ReturnType) -> syn::Type {
+    match output {
+        syn::ReturnType::Type(_, ty) => (**ty).clone(),
+        syn::ReturnType::Default => parse_quote! { () },
+    }
+}
+
+/*
+ * Turns a trait into a shared API contract for the procedures under
+ * `prefix`, one per method named `prefix.method` (or `#[wamp(rename)]`).
+ * Generates `<Trait>Client`, a caller stub whose methods make the calls,
+ * and `<Trait>Server`, which registers an implementation of the trait.
+ *
+ *   #[swampyer::interface(prefix = "com.example.thermostat")]
+ *   trait Thermostat {
+ *       async fn set_target(&self, celsius:f64) -> Result<(), RpcError>;
+ *       async fn target(&self) -> f64;
+ *   }
+ *
+ * The methods are rewritten to return `impl Future + Send`, which an
+ * `async fn` in the impl satisfies. Methods returning a Result send
+ * their Err the way #[procedure] does.
+ */
+#[proc_macro_attribute]
+pub fn interface(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(attr as syn::AttributeArgs);
+    let item = parse_macro_input!(item as syn::ItemTrait);
+
+    let mut prefix = None;
+    for arg in args.iter() {
+        match arg {
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("prefix") => {
+                match nv.lit {
+                    Lit::Str(ref s) => prefix = Some(s.value()),
+                    ref lit => return TokenStream::from(syn::Error::new_spanned(lit, "prefix takes a string").to_compile_error()),
+                }
+            },
+            other => return TokenStream::from(syn::Error::new_spanned(other, "unknown interface attribute").to_compile_error()),
+        }
+    }
+    let prefix = match prefix {
+        Some(prefix) => prefix,
+        None => return TokenStream::from(syn::Error::new(Span::call_site(), "interface needs a prefix = \"...\"").to_compile_error()),
+    };
+
+    match interface_tokens(prefix, item) {
+        Ok(tokens) => TokenStream::from(tokens),
+        Err(e) => TokenStream::from(e.to_compile_error()),
+    }
+}
+
+fn subscribe_tokens(topic:syn::LitStr, item:syn::ItemFn) -> syn::Result<TokenStream2> {
+    let sig = &item.sig;
+    if sig.asyncness.is_none() {
+        return Err(syn::Error::new_spanned(sig.fn_token, "subscribers have to be async"));
+    }
+    if !sig.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(&sig.generics, "subscribers can't be generic"));
+    }
+    if let syn::ReturnType::Type(_, ref ty) = sig.output {
+        return Err(syn::Error::new_spanned(ty, "subscribers can't return anything"));
+    }
+
+    // Everything is decoded before the handler runs so a bad event
+    // never gets half way through it
+    let mut decodes = Vec::new();
+    let mut params = Vec::new();
+    let mut position = 0usize;
+    for input in &sig.inputs {
+        let arg = match input {
+            syn::FnArg::Typed(arg) => arg,
+            syn::FnArg::Receiver(r) => return Err(syn::Error::new_spanned(r, "subscribers can't take self")),
+        };
+        let ty = &arg.ty;
+        match type_name(ty).as_deref() {
+            Some("WampClient") => params.push(quote! { client.clone() }),
+            Some("Event") => params.push(quote! { event.clone() }),
+            _ => {
+                let name = param_name(&arg.pat, position);
+                let binding = format_ident!("__arg{}", position);
+                decodes.push(quote! {
+                    let #binding = match ::swampyer::Subscriber::argument::< #ty >(&event, #position, #name) {
+                        Ok(value) => value,
+                        Err(e) => return ::swampyer::Subscriber::malformed(&client, event, e).await,
+                    };
+                });
+                params.push(quote! { #binding });
+                position += 1;
+            },
+        }
+    }
+
+    let ident = &sig.ident;
+    Ok(quote! {
+        #item
+
+        const _: () = {
+            fn __swampyer_handler(client: ::swampyer::WampClient, event: ::swampyer::Event) -> ::swampyer::BoxFuture<()> {
+                Box::pin(async move {
+                    #(#decodes)*
+                    #ident( #(#params),* ).await
+                })
+            }
+
+            ::swampyer::inventory::submit! {
+                ::swampyer::Subscriber::new(#topic, __swampyer_handler)
+            }
+        };
+    })
+}
+
+/*
+ * Subscribes an async fn to `topic`. Its parameters are decoded from the
+ * EVENT's args, falling back to the kwarg of the same name, and a
+ * `WampClient` or `Event` parameter gets the client or the raw event.
+ * Events that don't decode go to the client's onevent_error handler
+ * instead of the fn.
+ *
+ *   #[swampyer::subscribe("com.example.sensor.temperature")]
+ *   async fn temperature(sensor:String, celsius:f64) { ... }
+ *
+ * WampClient::subscribe_handlers subscribes all of them.
+ */
+#[proc_macro_attribute]
+pub fn subscribe(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let topic = parse_macro_input!(attr as syn::LitStr);
+    let item = parse_macro_input!(item as syn::ItemFn);
+    match subscribe_tokens(topic, item) {
+        Ok(tokens) => TokenStream::from(tokens),
+        Err(e) => TokenStream::from(e.to_compile_error()),
+    }
+}